@@ -5,28 +5,34 @@ use std::path::Path;
 
 use cu::pre::*;
 
+use super::user_config::UserConfig;
+use crate::WindowMode;
+
 #[derive(Debug, Clone)]
 pub struct EditorConfig {
     /// If the editor should use inherit stdio (i.e. if the editor is terminal-based
     pub inherit: bool,
     /// If the editor supports opening a directory
     pub supports_directory: bool,
+    /// If the editor supports opening multiple files in one invocation
+    pub supports_multiple_files: bool,
+    /// How to tell the editor to go to a line and column
+    pub goto: GotoStyle,
+    /// If viopen should wait for the editor to close the file
+    pub wait: bool,
     pub executable: String,
     pub executable_lower: String,
     pub args: Vec<String>,
 }
 
 impl EditorConfig {
-    /// Find the editor based on input, empty to find the editor on the system
-    pub fn find(editor: &str) -> cu::Result<Self> {
-        let editor = Self::find_internal(editor)?;
-        if editor.executable_lower.contains("notepad++") {
-            // has issues
-            cu::bail!("notepad++ is not supported");
-        }
-        Ok(editor)
-    }
-    fn find_internal(editor: &str) -> cu::Result<Self> {
+    /// Find the editor based on input.
+    ///
+    /// The precedence is: input (i.e. `VISUAL` or `EDITOR`) > user config file > search on the system.
+    ///
+    /// When searching on Linux without `DISPLAY` or `WAYLAND_DISPLAY`, GUI editors are skipped
+    /// unless `force_gui` is true
+    pub fn find(editor: &str, force_gui: bool) -> cu::Result<Self> {
         if !editor.is_empty() {
             match Self::resolve_from_spec(editor) {
                 Ok(Some(config)) => return Ok(config),
@@ -36,7 +42,34 @@ impl EditorConfig {
                 }
             }
         }
-        Self::find_on_system()
+        match UserConfig::load() {
+            Ok(Some(config)) => match Self::resolve_from_user_config(config) {
+                Ok(Some(config)) => return Ok(config),
+                Ok(None) => {}
+                Err(e) => {
+                    cu::warn!("failed to resolve editor from user config: {e:?}");
+                }
+            },
+            Ok(None) => {}
+            Err(e) => {
+                cu::warn!("failed to load user config: {e:?}");
+            }
+        }
+        Self::find_on_system(force_gui || is_gui_available())
+    }
+
+    fn resolve_from_user_config(config: UserConfig) -> cu::Result<Option<Self>> {
+        let Some(editor) = config.editor else {
+            return Ok(None);
+        };
+        let Some(mut resolved) = Self::resolve_from_spec(&editor)? else {
+            return Ok(None);
+        };
+        if let Some(inherit) = config.inherit {
+            resolved.inherit = inherit;
+        }
+        resolved.args.extend(config.args);
+        Ok(Some(resolved))
     }
 
     fn resolve_from_spec(editor: &str) -> cu::Result<Option<Self>> {
@@ -47,8 +80,6 @@ impl EditorConfig {
         }
         let args = cu::check!(shell_words::split(editor), "failed to split editor command")?;
 
-        // +4 for additional args that will be added, like the file path
-        let mut new_args = Vec::with_capacity(args.len() + 4);
         let mut args_iter = args.into_iter();
         let executable = cu::check!(args_iter.next(), "no executable found")?;
         let executable = cu::which(&executable)?;
@@ -58,20 +89,65 @@ impl EditorConfig {
             }
         }
         let executable = executable.into_utf8()?;
+        Ok(Some(Self::from_resolved_spec(executable, args_iter)))
+    }
+
+    /// Create the config from the resolved executable and the rest of the args
+    /// from the editor spec. User args are kept before the args added by viopen
+    fn from_resolved_spec(executable: String, args: impl ExactSizeIterator<Item = String>) -> Self {
+        // +4 for additional args that will be added, like the file path
+        let mut new_args = Vec::with_capacity(args.len() + 4);
         let editor_type = EditorType::guess(&executable);
 
         let inherit = match editor_type {
             EditorType::Notepad => {
-                new_args.extend(args_iter);
+                new_args.extend(args);
+                false
+            }
+            EditorType::Opener => {
+                new_args.extend(args);
+                // `open -a App` or `open -t` on macOS, wait for the app to exit
+                if cfg!(target_os = "macos")
+                    && new_args
+                        .iter()
+                        .any(|x| matches!(x.as_str(), "-a" | "-b" | "-e" | "-t"))
+                    && !new_args
+                        .iter()
+                        .any(|x| matches!(x.as_str(), "-W" | "--wait-apps"))
+                {
+                    new_args.push("-W".to_string());
+                }
+                false
+            }
+            EditorType::NotepadPlusPlus => {
+                new_args.extend(args);
+                // new instance without session so the process exits when
+                // the file is closed
+                for flag in ["-multiInst", "-nosession"] {
+                    if !new_args.iter().any(|x| x.eq_ignore_ascii_case(flag)) {
+                        new_args.push(flag.to_string());
+                    }
+                }
                 false
             }
             EditorType::Terminal { .. } => {
-                new_args.extend(args_iter);
+                new_args.extend(args);
                 true
             }
+            EditorType::EmacsClient => {
+                new_args.extend(args);
+                is_emacsclient_terminal(&new_args)
+            }
+            EditorType::BlockFlag { flag, .. } => {
+                new_args.extend(args);
+                if !new_args.iter().any(|x| x == flag) {
+                    new_args.push(flag.to_string());
+                }
+                false
+            }
             EditorType::WFlagOrWaitFlag { .. } => {
                 let mut found_wait_flag = false;
-                for arg in args_iter {
+                for arg in args {
                     if !found_wait_flag {
                         let l = arg.trim().to_lowercase();
                         if l.as_bytes() == b"-w" || l.as_bytes() == b"--wait" {
@@ -87,28 +163,41 @@ impl EditorConfig {
             }
         };
 
-        let config = Self::new(
+        Self::new(
             inherit,
             executable,
             editor_type.supports_directory(),
             new_args,
-        );
-
-        Ok(Some(config))
+        )
     }
-    fn find_on_system() -> cu::Result<EditorConfig> {
+    fn find_on_system(gui: bool) -> cu::Result<EditorConfig> {
+        if !gui {
+            cu::debug!("no display available, skipping GUI editors");
+        }
         // common ones - vi/emacs/code/subl
         if let Some(x) = find_executable_full_path("nvim") {
             return Ok(Self::inherit(x, true, vec![]));
         }
+        if let Some(x) = find_executable_full_path("emacsclient") {
+            // -a "" starts the daemon if it's not running
+            let mut args = vec!["-a".to_string(), String::new()];
+            return Ok(if gui {
+                args.push("-c".to_string());
+                Self::dont_inherit(x, true, args)
+            } else {
+                args.push("-t".to_string());
+                Self::inherit(x, true, args)
+            });
+        }
         if let Some(x) = find_executable_full_path("emacs") {
             return Ok(Self::inherit(x, true, vec![]));
         }
-        if let Some(x) = find_executable_full_path("code") {
-            return Ok(Self::dont_inherit(x, true, vec!["-w".to_string()]));
-        }
-        if let Some(x) = find_executable_full_path("subl") {
-            return Ok(Self::dont_inherit(x, true, vec!["-w".to_string()]));
+        if gui {
+            for x in ["code", "subl", "zed"] {
+                if let Some(x) = find_executable_full_path(x) {
+                    return Ok(Self::dont_inherit(x, true, vec!["-w".to_string()]));
+                }
+            }
         }
         if let Some(x) = find_executable_full_path("vi") {
             return Ok(Self::inherit(x, true, vec![]));
@@ -116,6 +205,16 @@ impl EditorConfig {
         if let Some(x) = find_executable_full_path("vim") {
             return Ok(Self::inherit(x, true, vec![]));
         }
+        for x in ["hx", "helix"] {
+            if let Some(x) = find_executable_full_path(x) {
+                return Ok(Self::inherit(x, true, vec![]));
+            }
+        }
+        for x in ["kak", "micro"] {
+            if let Some(x) = find_executable_full_path(x) {
+                return Ok(Self::inherit(x, false, vec![]));
+            }
+        }
         if let Some(x) = find_executable_full_path("xemacs") {
             return Ok(Self::inherit(x, true, vec![]));
         }
@@ -143,21 +242,105 @@ impl EditorConfig {
             }
         }
 
-        if cfg!(not(windows)) {
+        if gui {
+            for x in JETBRAINS_LAUNCHERS {
+                if let Some(x) = find_executable_full_path(x) {
+                    return Ok(Self::dont_inherit(x, true, vec!["--wait".to_string()]));
+                }
+            }
+        }
+
+        if cfg!(not(windows)) && gui {
             for x in ["vscode", "vsc", "sublime"] {
                 if let Some(x) = find_executable_full_path(x) {
                     return Ok(Self::dont_inherit(x, true, vec!["-w".to_string()]));
                 }
             }
-        } else {
+            if let Some(x) = find_executable_full_path("gedit") {
+                return Ok(Self::dont_inherit(x, false, vec!["--wait".to_string()]));
+            }
+            if let Some(x) = find_executable_full_path("kate") {
+                return Ok(Self::dont_inherit(x, false, vec!["--block".to_string()]));
+            }
+        } else if cfg!(windows) {
+            if let Some(x) = find_executable_full_path("notepad++") {
+                return Ok(Self::dont_inherit(
+                    x,
+                    false,
+                    vec!["-multiInst".to_string(), "-nosession".to_string()],
+                ));
+            }
             if let Some(x) = find_executable_full_path("notepad.exe") {
                 return Ok(Self::dont_inherit(x, false, vec![]));
             }
         }
 
+        if cfg!(target_os = "macos")
+            && let Some(x) = find_executable_full_path("open")
+        {
+            // open in the default text editor, and wait for it to exit
+            return Ok(Self::dont_inherit(
+                x,
+                false,
+                vec!["-t".to_string(), "-W".to_string()],
+            ));
+        }
+
         cu::bail!("failed to find compatible editor, please set the EDITOR environment variable");
     }
 
+    /// Find the pager for read-only viewing, from `PAGER`, or `less`/`more`
+    pub fn find_pager() -> Option<Self> {
+        if let Ok(pager) = cu::env_var("PAGER")
+            && !pager.is_empty()
+        {
+            match shell_words::split(&pager) {
+                Ok(args) => {
+                    let mut args = args.into_iter();
+                    if let Some(x) = args.next().and_then(|x| find_executable_full_path(&x)) {
+                        return Some(Self::inherit(x, false, args.collect()));
+                    }
+                }
+                Err(e) => {
+                    cu::warn!("failed to split PAGER: {e}");
+                }
+            }
+        }
+        for x in ["less", "more"] {
+            if let Some(x) = find_executable_full_path(x) {
+                return Some(Self::inherit(x, false, vec![]));
+            }
+        }
+        None
+    }
+
+    /// Get the platform opener that opens the file with the default application
+    /// (`xdg-open` on Linux, `open` on macOS, `start` on Windows)
+    pub fn default_opener() -> cu::Result<Self> {
+        let executable = if cfg!(windows) {
+            // handled by build_commands with Start-Process
+            "start".to_string()
+        } else {
+            let name = if cfg!(target_os = "macos") {
+                "open"
+            } else {
+                "xdg-open"
+            };
+            cu::check!(
+                find_executable_full_path(name),
+                "failed to find '{name}' to open with the default application"
+            )?
+        };
+        let mut config = Self::dont_inherit(executable, true, vec![]);
+        config.wait = false;
+        Ok(config)
+    }
+
+    /// If this is the platform opener from [`default_opener`](Self::default_opener)
+    pub fn is_default_opener(&self) -> bool {
+        matches!(EditorType::guess(&self.executable), EditorType::Opener)
+    }
+
     fn inherit(executable: impl Into<String>, supports_directory: bool, args: Vec<String>) -> Self {
         Self::new(true, executable, supports_directory, args)
     }
@@ -178,60 +361,388 @@ impl EditorConfig {
         Self {
             inherit,
             supports_directory,
+            supports_multiple_files: EditorType::guess(&s).supports_multiple_files(),
+            goto: GotoStyle::guess(&s),
+            wait: true,
             executable_lower: s.to_lowercase(),
             executable: s,
             args,
         }
     }
 
+    pub fn get_checked_file_paths(&self, files: &[&Path]) -> cu::Result<Vec<EditTarget>> {
+        if files.is_empty() {
+            cu::bail!("no file to edit");
+        }
+        files
+            .iter()
+            .map(|file| self.get_checked_file_path(file))
+            .collect()
+    }
+
+    /// Check the file or directory path, and parse the `path:line:col` or `path:line` suffix
+    /// if the path does not exist as-is
     #[cu::context("failed to check the file path to edit")]
-    pub fn get_checked_file_path(&self, file: &Path) -> cu::Result<String> {
+    pub fn get_checked_file_path(&self, file: &Path) -> cu::Result<EditTarget> {
+        let (file, line, column) = if file.exists() {
+            (file, None, None)
+        } else {
+            let (path, line, column) = split_position(file.as_utf8()?);
+            (Path::new(path), line, column)
+        };
         let file_str = if file.is_absolute() {
             file.as_utf8()?.to_string()
         } else {
             file.normalize()?.into_utf8()?
         };
-        if Path::new(&file_str).is_dir() && !self.supports_directory {
+        let is_dir = Path::new(&file_str).is_dir();
+        Ok(EditTarget {
+            path: file_str,
+            is_dir,
+            line,
+            column,
+        })
+    }
+
+    /// Add extra args (from `VIOPEN_ARGS`) before the file paths.
+    /// Wait flags are skipped if the args already have one (like `-w` and `--wait` for `code`),
+    /// so they are not duplicated
+    pub fn push_extra_args(&mut self, args: Vec<String>) {
+        let editor_type = EditorType::guess(&self.executable);
+        let mut has_wait_flag = self.args.iter().any(|x| editor_type.is_wait_flag(x));
+        for arg in args {
+            if editor_type.is_wait_flag(&arg) {
+                if has_wait_flag {
+                    continue;
+                }
+                has_wait_flag = true;
+            }
+            self.args.push(arg);
+        }
+    }
+
+    /// If running in WSL with a Windows editor (like `code.exe`), translate
+    /// the paths to Windows paths with `wslpath -w`
+    pub fn translate_wsl_paths(&self, targets: &mut [EditTarget]) -> cu::Result<()> {
+        if !cfg!(target_os = "linux") || !self.executable_lower.ends_with(".exe") || !is_wsl() {
+            return Ok(());
+        }
+        for target in targets {
+            target.path = wslpath(&target.path)?;
+        }
+        Ok(())
+    }
+
+    /// Check the editor can open the targets
+    pub fn check_targets(&self, targets: &[EditTarget]) -> cu::Result<()> {
+        if self.supports_directory {
+            return Ok(());
+        }
+        if let Some(target) = targets.iter().find(|x| x.is_dir) {
             cu::bail!(
                 "editor '{}' does not support editing directory: '{}' is a directory",
                 self.executable,
-                file_str
+                target.path
             );
         }
-        Ok(file_str)
+        Ok(())
+    }
+
+    /// Don't wait for the editor to close the file. Only GUI editors
+    /// can do this. Returns false if the editor will still block
+    pub fn set_no_wait(&mut self) -> bool {
+        match EditorType::guess(&self.executable) {
+            EditorType::Terminal { .. } => false,
+            EditorType::EmacsClient => {
+                // only new GUI frames can be left open
+                if self.inherit {
+                    return false;
+                }
+                if !self.args.iter().any(|x| x == "-n" || x == "--no-wait") {
+                    self.args.push("-n".to_string());
+                }
+                self.wait = false;
+                true
+            }
+            EditorType::WFlagOrWaitFlag { .. } => {
+                self.args.retain(|arg| {
+                    let l = arg.trim().to_lowercase();
+                    l != "-w" && l != "--wait"
+                });
+                self.wait = false;
+                true
+            }
+            EditorType::BlockFlag { flag, .. } => {
+                self.args.retain(|arg| arg != flag);
+                self.wait = false;
+                true
+            }
+            EditorType::Opener => {
+                // macOS `open -W`
+                self.args.retain(|arg| arg != "-W" && arg != "--wait-apps");
+                self.wait = false;
+                true
+            }
+            EditorType::Notepad | EditorType::NotepadPlusPlus => {
+                self.wait = false;
+                true
+            }
+        }
+    }
+
+    /// Set if the editor should open a new window or reuse an existing one.
+    /// Returns false if the editor doesn't support it
+    pub fn set_window(&mut self, mode: WindowMode) -> bool {
+        let file_name = executable_name(&self.executable).to_ascii_lowercase();
+        let flag = match (file_name.as_str(), mode) {
+            (_, WindowMode::Default) => return true,
+            ("code" | "vscode" | "vsc", WindowMode::New) => "-n",
+            ("code" | "vscode" | "vsc", WindowMode::Reuse) => "-r",
+            ("subl" | "sublime", WindowMode::New) => "-n",
+            // Sublime Text reuses the window by default
+            ("subl" | "sublime", WindowMode::Reuse) => return true,
+            ("zed", WindowMode::New) => "-n",
+            ("zed", WindowMode::Reuse) => "-a",
+            _ => return false,
+        };
+        if !self.args.iter().any(|x| x == flag) {
+            self.args.push(flag.to_string());
+        }
+        true
+    }
+
+    /// Search for the pattern when opening. Returns false if the editor doesn't support it
+    pub fn set_search(&mut self, pattern: &str) -> bool {
+        // +/pattern applies to the first file
+        if self.goto != GotoStyle::PlusLine {
+            return false;
+        }
+        self.args.push(format!("+/{pattern}"));
+        true
+    }
+
+    /// Open the files in view-only mode with the editor's native readonly flag.
+    /// Returns false if the editor doesn't have one
+    pub fn set_readonly(&mut self) -> bool {
+        let file_name = executable_name(&self.executable);
+        let flags: &[&str] = match file_name.to_ascii_lowercase().as_str() {
+            "vi" | "vim" | "nvim" | "neovim" | "gvim" | "nvi" | "elvis" | "vile" => &["-R"],
+            "nano" => &["-v"],
+            "kak" => &["-ro"],
+            "micro" => &["-readonly", "true"],
+            "notepad++" => &["-ro"],
+            // pagers are always read-only
+            "less" | "more" | "most" | "bat" => return true,
+            _ => return false,
+        };
+        if !self.args.iter().any(|x| x == flags[0]) {
+            self.args.extend(flags.iter().map(|x| x.to_string()));
+        }
+        true
     }
+
+    /// Add the arguments to open the targets to the editor args
+    pub fn push_target_args(&mut self, targets: Vec<EditTarget>) {
+        if self.goto == GotoStyle::VsCode && targets.iter().any(|x| x.line.is_some() && !x.is_dir) {
+            // --goto applies to all paths
+            self.args.push("--goto".to_string());
+        }
+        for target in targets {
+            let Some(line) = target.line.filter(|_| !target.is_dir) else {
+                self.args.push(target.path);
+                continue;
+            };
+            match self.goto {
+                GotoStyle::None => self.args.push(target.path),
+                GotoStyle::PlusLine => {
+                    self.args.push(format!("+{line}"));
+                    self.args.push(target.path);
+                }
+                GotoStyle::PlusLineColumn => {
+                    match target.column {
+                        Some(column) => self.args.push(format!("+{line}:{column}")),
+                        None => self.args.push(format!("+{line}")),
+                    }
+                    self.args.push(target.path);
+                }
+                GotoStyle::PlusLineCommaColumn => {
+                    match target.column {
+                        Some(column) => self.args.push(format!("+{line},{column}")),
+                        None => self.args.push(format!("+{line}")),
+                    }
+                    self.args.push(target.path);
+                }
+                GotoStyle::NotepadPlusPlus => {
+                    self.args.push(format!("-n{line}"));
+                    if let Some(column) = target.column {
+                        self.args.push(format!("-c{column}"));
+                    }
+                    self.args.push(target.path);
+                }
+                GotoStyle::JetBrains => {
+                    self.args.push("--line".to_string());
+                    self.args.push(line.to_string());
+                    if let Some(column) = target.column {
+                        self.args.push("--column".to_string());
+                        self.args.push(column.to_string());
+                    }
+                    self.args.push(target.path);
+                }
+                GotoStyle::VsCode | GotoStyle::Suffix => {
+                    let arg = match target.column {
+                        Some(column) => format!("{}:{line}:{column}", target.path),
+                        None => format!("{}:{line}", target.path),
+                    };
+                    self.args.push(arg);
+                }
+            }
+        }
+    }
+}
+
+/// A path to edit, with an optional position to go to
+#[derive(Debug, Clone)]
+pub struct EditTarget {
+    pub path: String,
+    /// If the path is an existing directory
+    pub is_dir: bool,
+    pub line: Option<u32>,
+    pub column: Option<u32>,
+}
+
+impl std::fmt::Display for EditTarget {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.path)?;
+        if let Some(line) = self.line {
+            write!(f, ":{line}")?;
+            if let Some(column) = self.column {
+                write!(f, ":{column}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Split `path:line:col` or `path:line` into the parts
+fn split_position(file: &str) -> (&str, Option<u32>, Option<u32>) {
+    let Some((rest, last)) = file.rsplit_once(':') else {
+        return (file, None, None);
+    };
+    let Ok(last) = last.parse::<u32>() else {
+        return (file, None, None);
+    };
+    if let Some((path, line)) = rest.rsplit_once(':')
+        && let Ok(line) = line.parse::<u32>()
+        && is_valid_path_part(path)
+    {
+        return (path, Some(line), Some(last));
+    }
+    if is_valid_path_part(rest) {
+        return (rest, Some(last), None);
+    }
+    (file, None, None)
+}
+
+/// Check the path part after splitting the position is not empty,
+/// and not just a drive letter on Windows (like `C:`)
+fn is_valid_path_part(path: &str) -> bool {
+    if path.is_empty() {
+        return false;
+    }
+    if cfg!(windows) && path.len() == 1 && path.as_bytes()[0].is_ascii_alphabetic() {
+        return false;
+    }
+    true
 }
 
 pub enum EditorType {
-    Terminal { supports_directory: bool },
-    WFlagOrWaitFlag { supports_directory: bool },
+    Terminal {
+        supports_directory: bool,
+    },
+    WFlagOrWaitFlag {
+        supports_directory: bool,
+    },
+    /// GUI editor that needs a flag to block until the file is closed,
+    /// like `gedit --wait` or `kate --block`
+    BlockFlag {
+        flag: &'static str,
+        supports_directory: bool,
+    },
     Notepad,
+    NotepadPlusPlus,
+    /// The platform opener, which opens the file with the default application
+    Opener,
+    /// `emacsclient`, which is a terminal editor with `-t`/`-nw`, or a GUI editor with `-c`
+    EmacsClient,
 }
 impl EditorType {
+    /// If the arg makes the editor wait for the file to be closed.
+    /// Matched the same way as when resolving the editor spec
+    fn is_wait_flag(&self, arg: &str) -> bool {
+        match self {
+            Self::WFlagOrWaitFlag { .. } => {
+                let l = arg.trim().to_lowercase();
+                l == "-w" || l == "--wait"
+            }
+            Self::BlockFlag { flag, .. } => arg == *flag,
+            Self::Opener => arg == "-W" || arg == "--wait-apps",
+            _ => false,
+        }
+    }
+
     fn guess(executable: &str) -> Self {
-        let mut file_name = match executable.rfind(['/', '\\']) {
-            None => executable,
-            Some(i) => &executable[i + 1..],
+        let file_name = executable_name(executable);
+        let opener = if cfg!(windows) {
+            "start"
+        } else if cfg!(target_os = "macos") {
+            "open"
+        } else {
+            "xdg-open"
         };
-        while let Some(i) = file_name.rfind('.') {
-            file_name = &file_name[..i];
+        if file_name.eq_ignore_ascii_case(opener) {
+            return EditorType::Opener;
         }
-        let file_name = file_name.trim();
-        for n in ["code", "vscode", "vsc", "sublime", "subl"] {
+        for n in ["code", "vscode", "vsc", "sublime", "subl", "zed"] {
             if file_name.eq_ignore_ascii_case(n) {
                 return EditorType::WFlagOrWaitFlag {
                     supports_directory: true,
                 };
             }
         }
-        if file_name.eq_ignore_ascii_case("notepad") {
-            return EditorType::Notepad;
+        if file_name.eq_ignore_ascii_case("emacsclient") {
+            return EditorType::EmacsClient;
         }
-        if file_name.eq_ignore_ascii_case("nano") {
-            return EditorType::Terminal {
+        if file_name.eq_ignore_ascii_case("gedit") {
+            return EditorType::BlockFlag {
+                flag: "--wait",
+                supports_directory: false,
+            };
+        }
+        if file_name.eq_ignore_ascii_case("kate") {
+            return EditorType::BlockFlag {
+                flag: "--block",
                 supports_directory: false,
             };
         }
+        if is_jetbrains(file_name) {
+            return EditorType::BlockFlag {
+                flag: "--wait",
+                supports_directory: true,
+            };
+        }
+        if file_name.eq_ignore_ascii_case("notepad") {
+            return EditorType::Notepad;
+        }
+        if file_name.eq_ignore_ascii_case("notepad++") {
+            return EditorType::NotepadPlusPlus;
+        }
+        for n in ["nano", "kak", "micro"] {
+            if file_name.eq_ignore_ascii_case(n) {
+                return EditorType::Terminal {
+                    supports_directory: false,
+                };
+            }
+        }
 
         EditorType::Terminal {
             supports_directory: true,
@@ -242,9 +753,156 @@ impl EditorType {
         match self {
             EditorType::Terminal { supports_directory } => *supports_directory,
             EditorType::WFlagOrWaitFlag { supports_directory } => *supports_directory,
-            EditorType::Notepad => false,
+            EditorType::BlockFlag {
+                supports_directory, ..
+            } => *supports_directory,
+            EditorType::Notepad | EditorType::NotepadPlusPlus => false,
+            EditorType::Opener | EditorType::EmacsClient => true,
         }
     }
+
+    pub fn supports_multiple_files(&self) -> bool {
+        !matches!(self, EditorType::Notepad | EditorType::Opener)
+    }
+}
+
+/// How an editor accepts the line and column to go to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GotoStyle {
+    /// Not supported, the file is opened normally
+    None,
+    /// `+line path` (vi family, less, more)
+    PlusLine,
+    /// `+line:col path` (Kakoune, micro, gedit, emacsclient)
+    PlusLineColumn,
+    /// `+line,col path` (nano)
+    PlusLineCommaColumn,
+    /// `--goto path:line:col` (VS Code)
+    VsCode,
+    /// `path:line:col` (Sublime Text, Zed, Helix)
+    Suffix,
+    /// `-nline -ccol path` (Notepad++)
+    NotepadPlusPlus,
+    /// `--line line --column col path` (JetBrains IDEs)
+    JetBrains,
+}
+impl GotoStyle {
+    fn guess(executable: &str) -> Self {
+        let file_name = executable_name(executable);
+        for n in [
+            "vi", "vim", "nvim", "neovim", "gvim", "nvi", "elvis", "vile", "less", "more",
+        ] {
+            if file_name.eq_ignore_ascii_case(n) {
+                return GotoStyle::PlusLine;
+            }
+        }
+        for n in ["kak", "micro", "gedit", "emacsclient"] {
+            if file_name.eq_ignore_ascii_case(n) {
+                return GotoStyle::PlusLineColumn;
+            }
+        }
+        if file_name.eq_ignore_ascii_case("nano") {
+            return GotoStyle::PlusLineCommaColumn;
+        }
+        for n in ["code", "vscode", "vsc"] {
+            if file_name.eq_ignore_ascii_case(n) {
+                return GotoStyle::VsCode;
+            }
+        }
+        for n in ["sublime", "subl", "zed", "hx", "helix"] {
+            if file_name.eq_ignore_ascii_case(n) {
+                return GotoStyle::Suffix;
+            }
+        }
+        if file_name.eq_ignore_ascii_case("notepad++") {
+            return GotoStyle::NotepadPlusPlus;
+        }
+        if is_jetbrains(file_name) {
+            return GotoStyle::JetBrains;
+        }
+        GotoStyle::None
+    }
+}
+
+/// Check if the executable is a JetBrains IDE launcher, like `idea` or `pycharm`.
+/// The Windows launchers are named like `idea64.exe`
+fn is_jetbrains(file_name: &str) -> bool {
+    let file_name = file_name.strip_suffix("64").unwrap_or(file_name);
+    JETBRAINS_LAUNCHERS
+        .iter()
+        .any(|x| file_name.eq_ignore_ascii_case(x))
+}
+
+static JETBRAINS_LAUNCHERS: &[&str] = &[
+    "idea",
+    "pycharm",
+    "clion",
+    "goland",
+    "webstorm",
+    "phpstorm",
+    "rubymine",
+    "rider",
+    "rustrover",
+];
+
+/// Get the file name of the executable without extensions
+fn executable_name(executable: &str) -> &str {
+    let mut file_name = match executable.rfind(['/', '\\']) {
+        None => executable,
+        Some(i) => &executable[i + 1..],
+    };
+    while let Some(i) = file_name.rfind('.') {
+        file_name = &file_name[..i];
+    }
+    file_name.trim()
+}
+
+/// Check if the emacsclient args should be treated as a terminal editor.
+/// Only `-c` without `-t`/`-nw` creates a GUI frame
+fn is_emacsclient_terminal(args: &[String]) -> bool {
+    let has = |flags: &[&str]| args.iter().any(|x| flags.contains(&x.as_str()));
+    !has(&["-c", "--create-frame"]) || has(&["-t", "-nw", "--tty"])
+}
+
+/// Check if GUI editors can be displayed. Only Linux is checked,
+/// since a display is always available on Windows and macOS
+fn is_gui_available() -> bool {
+    if !cfg!(target_os = "linux") {
+        return true;
+    }
+    ["DISPLAY", "WAYLAND_DISPLAY"]
+        .into_iter()
+        .any(|x| std::env::var_os(x).is_some_and(|x| !x.is_empty()))
+}
+
+/// Check if running inside WSL
+fn is_wsl() -> bool {
+    std::env::var_os("WSL_DISTRO_NAME").is_some()
+        || Path::new("/proc/sys/fs/binfmt_misc/WSLInterop").exists()
+}
+
+/// Translate the WSL path to a Windows path with `wslpath -w`
+fn wslpath(path: &str) -> cu::Result<String> {
+    let output = cu::check!(
+        std::process::Command::new("wslpath")
+            .arg("-w")
+            .arg(path)
+            .output(),
+        "failed to run wslpath"
+    )?;
+    if !output.status.success() {
+        cu::bail!(
+            "wslpath failed to translate '{path}': {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    let translated = cu::check!(
+        String::from_utf8(output.stdout),
+        "wslpath returned a non-utf8 path"
+    )?;
+    let translated = translated.trim_end().to_string();
+    cu::trace!("translated '{path}' to '{translated}'");
+    Ok(translated)
 }
 
 fn find_executable_full_path(executable: &str) -> Option<String> {
@@ -259,3 +917,117 @@ fn find_executable_full_path(executable: &str) -> Option<String> {
         Ok(x) => Some(x),
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    #[test]
+    fn test_split_position() {
+        assert_eq!(split_position("foo.rs"), ("foo.rs", None, None));
+        assert_eq!(split_position("foo.rs:42"), ("foo.rs", Some(42), None));
+        assert_eq!(
+            split_position("foo.rs:42:10"),
+            ("foo.rs", Some(42), Some(10))
+        );
+        assert_eq!(split_position("foo:bar.rs"), ("foo:bar.rs", None, None));
+        assert_eq!(split_position(":42"), (":42", None, None));
+        assert_eq!(
+            split_position("C:\\foo.rs:42:10"),
+            ("C:\\foo.rs", Some(42), Some(10))
+        );
+    }
+
+    #[test]
+    fn test_guess_helix() {
+        for x in ["/usr/bin/hx", "C:\\bin\\hx.exe", "helix"] {
+            assert!(matches!(
+                EditorType::guess(x),
+                EditorType::Terminal {
+                    supports_directory: true
+                }
+            ));
+            assert_eq!(GotoStyle::guess(x), GotoStyle::Suffix);
+        }
+    }
+
+    #[test]
+    fn test_kakoune() {
+        assert!(matches!(
+            EditorType::guess("/usr/bin/kak"),
+            EditorType::Terminal {
+                supports_directory: false
+            }
+        ));
+        let mut editor = EditorConfig::inherit("/usr/bin/kak", false, vec![]);
+        assert!(editor.args.is_empty());
+        editor.push_target_args(vec![EditTarget {
+            path: "/foo.rs".to_string(),
+            is_dir: false,
+            line: Some(42),
+            column: Some(10),
+        }]);
+        assert_eq!(editor.args, ["+42:10", "/foo.rs"]);
+    }
+
+    #[test]
+    fn test_nano() {
+        assert_eq!(
+            GotoStyle::guess("/usr/bin/nano"),
+            GotoStyle::PlusLineCommaColumn
+        );
+        let mut editor = EditorConfig::inherit("/usr/bin/nano", false, vec![]);
+        editor.push_target_args(vec![
+            EditTarget {
+                path: "/foo.rs".to_string(),
+                is_dir: false,
+                line: Some(42),
+                column: Some(10),
+            },
+            EditTarget {
+                path: "/bar.rs".to_string(),
+                is_dir: false,
+                line: Some(7),
+                column: None,
+            },
+        ]);
+        assert_eq!(editor.args, ["+42,10", "/foo.rs", "+7", "/bar.rs"]);
+    }
+
+    #[test]
+    fn test_micro_keeps_user_args() {
+        let mut editor = EditorConfig::from_resolved_spec(
+            "/usr/bin/micro".to_string(),
+            ["+10".to_string()].into_iter(),
+        );
+        assert!(editor.inherit);
+        assert!(!editor.supports_directory);
+        editor.push_target_args(vec![EditTarget {
+            path: "/foo.txt".to_string(),
+            is_dir: false,
+            line: None,
+            column: None,
+        }]);
+        assert_eq!(editor.args, ["+10", "/foo.txt"]);
+    }
+
+    #[test]
+    fn test_push_extra_args_wait_flag() {
+        let mut editor =
+            EditorConfig::from_resolved_spec("/usr/bin/code".to_string(), std::iter::empty());
+        assert_eq!(editor.args, ["-w"]);
+        editor.push_extra_args(vec!["--wait".to_string(), "--new-window".to_string()]);
+        assert_eq!(editor.args, ["-w", "--new-window"]);
+
+        let mut editor = EditorConfig::from_resolved_spec(
+            "/usr/bin/code".to_string(),
+            ["--wait".to_string()].into_iter(),
+        );
+        editor.push_extra_args(vec!["-w".to_string(), "-W".to_string()]);
+        assert_eq!(editor.args, ["--wait"]);
+
+        let mut editor =
+            EditorConfig::from_resolved_spec("/usr/bin/kate".to_string(), std::iter::empty());
+        editor.push_extra_args(vec!["--block".to_string(), "-w".to_string()]);
+        assert_eq!(editor.args, ["--block", "-w"]);
+    }
+}
@@ -6,90 +6,426 @@ use std::path::Path;
 use cu::pre::*;
 
 mod editor_config;
-use editor_config::EditorConfig;
+use editor_config::{EditTarget, EditorConfig};
+mod user_config;
 
-pub fn open_internal(editor: &str, file: &Path) -> cu::Result<()> {
-    let editor = EditorConfig::find(editor)?;
-    let file_str = editor.get_checked_file_path(file)?;
-    cu::check!(
-        spawn_editor(editor, file_str.clone()),
-        "failed to spawn editor for path '{file_str}'"
-    )
+use crate::{EditorInfo, OpenOptions};
+
+pub fn detect_editor(editor: &str) -> cu::Result<EditorInfo> {
+    let editor = EditorConfig::find(editor, false)?;
+    Ok(EditorInfo {
+        executable: editor.executable,
+        inherit: editor.inherit,
+        args: editor.args,
+        supports_directory: editor.supports_directory,
+        supports_multiple_files: editor.supports_multiple_files,
+    })
+}
+
+pub fn open_internal(editor: &str, files: &[&Path], options: &OpenOptions) -> cu::Result<()> {
+    open_status_internal(editor, files, options)?;
+    Ok(())
+}
+
+/// Open the files and return the exit code of the editor. If the editor is spawned
+/// multiple times, the first nonzero exit code is returned
+pub fn open_status_internal(
+    editor: &str,
+    files: &[&Path],
+    options: &OpenOptions,
+) -> cu::Result<i32> {
+    if options.timeout.is_some() {
+        cu::bail!("timeout is only supported by the co_* functions with the `coroutine` feature");
+    }
+    let (editor, targets) = prepare(editor, files, options)?;
+    if editor.supports_multiple_files || targets.len() == 1 {
+        let paths = join_targets(&targets);
+        return cu::check!(
+            spawn_editor(editor, targets, options),
+            "failed to spawn editor for path(s) {paths}"
+        );
+    }
+    // open one by one if the editor doesn't support multiple files
+    let mut status = 0;
+    for target in targets {
+        let path = target.to_string();
+        let code = cu::check!(
+            spawn_editor(editor.clone(), vec![target], options),
+            "failed to spawn editor for path '{path}'"
+        )?;
+        if status == 0 {
+            status = code;
+        }
+    }
+    Ok(status)
 }
 
 #[cfg(feature = "coroutine")]
-pub async fn co_open_internal(editor: &str, file: &Path) -> cu::Result<()> {
-    let editor = EditorConfig::find(editor)?;
-    let file_str = editor.get_checked_file_path(file)?;
-    cu::check!(
-        co_spawn_editor(editor, file_str.clone()).await,
-        "failed to spawn editor for path '{file_str}'"
-    )
+pub async fn co_open_internal(
+    editor: &str,
+    files: &[&Path],
+    options: &OpenOptions,
+) -> cu::Result<()> {
+    co_open_status_internal(editor, files, options).await?;
+    Ok(())
 }
 
-fn spawn_editor(mut editor: EditorConfig, path: String) -> cu::Result<()> {
-    cu::trace!("spawning editor: {:?} for path {}", editor, path);
-    #[cfg(windows)]
-    {
-        if editor.executable_lower.ends_with(".cmd") {
-            editor.args.push(path);
-            configure_and_spawn_powershell! {
-                inherit: editor.inherit,
-                executable: editor.executable,
-                args: &editor.args,
-                command => { command.wait()?; }
+/// Open the files and return the exit code of the editor. See [`open_status_internal`]
+#[cfg(feature = "coroutine")]
+pub async fn co_open_status_internal(
+    editor: &str,
+    files: &[&Path],
+    options: &OpenOptions,
+) -> cu::Result<i32> {
+    let (editor, targets) = prepare(editor, files, options)?;
+    if editor.supports_multiple_files || targets.len() == 1 {
+        let paths = join_targets(&targets);
+        return cu::check!(
+            co_spawn_editor(editor, targets, options).await,
+            "failed to spawn editor for path(s) {paths}"
+        );
+    }
+    if editor.inherit {
+        // terminal editors share the terminal, so they must be opened one by one
+        let mut status = 0;
+        for target in targets {
+            let path = target.to_string();
+            let code = cu::check!(
+                co_spawn_editor(editor.clone(), vec![target], options).await,
+                "failed to spawn editor for path '{path}'"
+            )?;
+            if status == 0 {
+                status = code;
             }
-            return Ok(());
         }
-        if editor.executable_lower.ends_with("notepad.exe") {
-            configure_and_spawn_powershell_for_path_and_wait! {
-                executable: editor.executable,
-                path: path,
-                command => { command.wait()?; }
+        return Ok(status);
+    }
+    // GUI editors spawn independent processes, so open all of them at once
+    let handles = targets.into_iter().map(|target| {
+        let editor = editor.clone();
+        let options = options.clone();
+        cu::co::spawn(async move {
+            let path = target.to_string();
+            (path, co_spawn_editor(editor, vec![target], &options).await)
+        })
+    });
+    let mut set = cu::co::set(handles);
+    let mut failed = Vec::new();
+    let mut status = 0;
+    while let Some(joined) = set.next().await {
+        let (path, result) = joined?;
+        match result {
+            Ok(code) => {
+                if status == 0 {
+                    status = code;
+                }
+            }
+            Err(e) => {
+                cu::error!("failed to spawn editor for path '{path}': {e:?}");
+                failed.push(path);
             }
-            return Ok(());
         }
     }
-    editor.args.push(path);
-    configure_and_spawn! {
-        inherit: editor.inherit,
-        command: Path::new(&editor.executable).command().args(editor.args),
-        command => { command.wait()?; }
+    if !failed.is_empty() {
+        cu::bail!(
+            "failed to spawn editor for {} path(s): {}",
+            failed.len(),
+            failed.join(", ")
+        );
+    }
+    Ok(status)
+}
+
+/// Find the editor and check the targets
+fn prepare(
+    editor: &str,
+    files: &[&Path],
+    options: &OpenOptions,
+) -> cu::Result<(EditorConfig, Vec<EditTarget>)> {
+    let editor = configure_editor(editor, options)?;
+    let targets = get_targets(&editor, files, options)?;
+    if options.open_with_default
+        && !editor.is_default_opener()
+        && let Some(target) = targets
+            .iter()
+            .find(|x| !x.is_dir && !is_text_file(Path::new(&x.path)))
+    {
+        cu::debug!("'{target}' is not a text file, opening with the default application");
+        return Ok((EditorConfig::default_opener()?, targets));
+    }
+    Ok((editor, targets))
+}
+
+fn configure_editor(editor: &str, options: &OpenOptions) -> cu::Result<EditorConfig> {
+    let mut editor = match EditorConfig::find(editor, options.force_gui) {
+        Ok(editor) => editor,
+        Err(e) => {
+            if options.readonly
+                && let Some(pager) = EditorConfig::find_pager()
+            {
+                cu::debug!("{e:?}");
+                cu::info!("no editor found, viewing with '{}'", pager.executable);
+                pager
+            } else if options.open_with_default {
+                cu::debug!("{e:?}");
+                cu::info!("no editor found, opening with the default application");
+                EditorConfig::default_opener()?
+            } else {
+                return Err(e);
+            }
+        }
+    };
+    if let Ok(extra_args) = cu::env_var("VIOPEN_ARGS")
+        && !extra_args.is_empty()
+    {
+        let extra_args = cu::check!(
+            shell_words::split(&extra_args),
+            "failed to split VIOPEN_ARGS"
+        )?;
+        editor.push_extra_args(extra_args);
+    }
+    if !editor.set_window(options.window) {
+        cu::warn!(
+            "'{}' does not support choosing the window, using its default behavior",
+            editor.executable
+        );
+    }
+    if options.readonly && !editor.set_readonly() {
+        cu::warn!(
+            "'{}' does not have a readonly mode, opening normally",
+            editor.executable
+        );
+    }
+    if let Some(pattern) = &options.search
+        && !editor.set_search(pattern)
+    {
+        cu::warn!(
+            "'{}' does not support opening at a search pattern, ignoring it",
+            editor.executable
+        );
+    }
+    if options.no_wait && !editor.set_no_wait() {
+        cu::warn!(
+            "'{}' is a terminal editor, it will still block until closed",
+            editor.executable
+        );
+    }
+    Ok(editor)
+}
+
+fn get_targets(
+    editor: &EditorConfig,
+    files: &[&Path],
+    options: &OpenOptions,
+) -> cu::Result<Vec<EditTarget>> {
+    let mut targets = editor.get_checked_file_paths(files)?;
+    if let Some(line) = options.line {
+        for target in &mut targets {
+            target.line = Some(line);
+            target.column = options.column;
+        }
+    }
+    if options.create && !options.dry_run {
+        for target in targets.iter().filter(|x| !x.is_dir) {
+            create_file(Path::new(&target.path))?;
+        }
     }
+    editor.translate_wsl_paths(&mut targets)?;
+    Ok(targets)
+}
+
+/// Create the file and parent directories if the file doesn't exist
+fn create_file(path: &Path) -> cu::Result<()> {
+    if path.exists() {
+        return Ok(());
+    }
+    if let Some(parent) = path.parent() {
+        cu::check!(
+            std::fs::create_dir_all(parent),
+            "failed to create directory '{}'",
+            parent.display()
+        )?;
+    }
+    cu::debug!("creating '{}'", path.display());
+    cu::check!(
+        std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path),
+        "failed to create file '{}'",
+        path.display()
+    )?;
     Ok(())
 }
 
-#[cfg(feature = "coroutine")]
-async fn co_spawn_editor(mut editor: EditorConfig, path: String) -> cu::Result<()> {
-    cu::trace!("spawning editor: {:?} for path {}", editor, path);
+/// Check if the file looks like a text file, by checking for NUL bytes
+/// at the start of the file. Files that can't be read are treated as text
+fn is_text_file(path: &Path) -> bool {
+    use std::io::Read;
+    let Ok(file) = std::fs::File::open(path) else {
+        return true;
+    };
+    let mut buf = Vec::with_capacity(8000);
+    if file.take(8000).read_to_end(&mut buf).is_err() {
+        return true;
+    }
+    !buf.contains(&0)
+}
+
+fn join_targets(targets: &[EditTarget]) -> String {
+    let targets = targets.iter().map(|x| format!("'{x}'")).collect::<Vec<_>>();
+    targets.join(", ")
+}
+
+/// A command to spawn for the editor
+#[derive(Debug)]
+struct SpawnCommand {
+    inherit: bool,
+    executable: String,
+    args: Vec<String>,
+}
+
+impl std::fmt::Display for SpawnCommand {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let command = std::iter::once(&self.executable).chain(&self.args);
+        write!(f, "{}", shell_words::join(command))
+    }
+}
+
+/// Build the commands to spawn to open the targets
+fn build_commands(
+    mut editor: EditorConfig,
+    targets: Vec<EditTarget>,
+) -> cu::Result<Vec<SpawnCommand>> {
+    editor.check_targets(&targets)?;
     #[cfg(windows)]
     {
         if editor.executable_lower.ends_with(".cmd") {
-            editor.args.push(path);
-            configure_and_spawn_powershell! {
-                inherit: editor.inherit,
-                executable: editor.executable,
-                args: &editor.args,
-                command => { command.co_wait().await?; }
-            }
-            return Ok(());
+            editor.push_target_args(targets);
+            return Ok(vec![powershell_call(
+                editor.inherit,
+                &editor.executable,
+                &editor.args,
+            )]);
         }
         if editor.executable_lower.ends_with("notepad.exe") {
-            configure_and_spawn_powershell_for_path_and_wait! {
-                executable: editor.executable,
-                path: path,
-                command => { command.co_wait().await?; }
-            }
-            return Ok(());
+            // notepad only supports one file, and does not support goto
+            return targets
+                .into_iter()
+                .map(|target| {
+                    powershell_start_process(&editor.executable, &[target.path], editor.wait)
+                })
+                .collect();
+        }
+        if editor.is_default_opener() {
+            return targets
+                .into_iter()
+                .map(|target| powershell_start_default(&target.path, editor.wait))
+                .collect();
+        }
+        if editor.executable_lower.ends_with("notepad++.exe") {
+            editor.push_target_args(targets);
+            return Ok(vec![powershell_start_process(
+                &editor.executable,
+                &editor.args,
+                editor.wait,
+            )?]);
         }
     }
-    editor.args.push(path);
-    configure_and_spawn! {
+    editor.push_target_args(targets);
+    Ok(vec![SpawnCommand {
         inherit: editor.inherit,
-        command: Path::new(&editor.executable).command().args(editor.args),
-        command => { command.co_wait().await?; }
+        executable: editor.executable,
+        args: editor.args,
+    }])
+}
+
+/// Spawn the editor and return the first nonzero exit code, or 0
+fn spawn_editor(
+    editor: EditorConfig,
+    targets: Vec<EditTarget>,
+    options: &OpenOptions,
+) -> cu::Result<i32> {
+    cu::trace!("spawning editor: {:?} for targets {:?}", editor, targets);
+    let mut status = 0;
+    for spawn in build_commands(editor, targets)? {
+        if options.dry_run {
+            println!("{spawn}");
+            continue;
+        }
+        let exit_status = configure_and_spawn! {
+            inherit: spawn.inherit,
+            command: Path::new(&spawn.executable).command().args(spawn.args),
+            command => { command.wait()? }
+        };
+        if status == 0 {
+            status = exit_code(exit_status);
+        }
     }
-    Ok(())
+    Ok(status)
+}
+
+#[cfg(feature = "coroutine")]
+async fn co_spawn_editor(
+    editor: EditorConfig,
+    targets: Vec<EditTarget>,
+    options: &OpenOptions,
+) -> cu::Result<i32> {
+    cu::trace!("spawning editor: {:?} for targets {:?}", editor, targets);
+    let mut status = 0;
+    for spawn in build_commands(editor, targets)? {
+        if options.dry_run {
+            println!("{spawn}");
+            continue;
+        }
+        let exit_status = match options.timeout {
+            Some(timeout) => co_spawn_with_timeout(spawn, timeout, options.kill_on_timeout).await?,
+            None => configure_and_spawn! {
+                inherit: spawn.inherit,
+                command: Path::new(&spawn.executable).command().args(spawn.args),
+                command => { command.co_wait().await? }
+            },
+        };
+        if status == 0 {
+            status = exit_code(exit_status);
+        }
+    }
+    Ok(status)
+}
+
+/// Get the exit code from the status. -1 is returned if the process was terminated
+/// by a signal
+fn exit_code(status: std::process::ExitStatus) -> i32 {
+    status.code().unwrap_or(-1)
+}
+
+/// Spawn the command and wait for it to exit, with a timeout
+#[cfg(feature = "coroutine")]
+async fn co_spawn_with_timeout(
+    spawn: SpawnCommand,
+    timeout: std::time::Duration,
+    kill_on_timeout: bool,
+) -> cu::Result<std::process::ExitStatus> {
+    let mut child = configure_and_spawn! {
+        inherit: spawn.inherit,
+        command: Path::new(&spawn.executable).command().args(spawn.args),
+        command => { command.co_spawn().await? }
+    };
+    if child.co_wait_timeout(timeout).await?.is_some() {
+        // wait again to finish the IO of the child
+        return child.co_wait().await;
+    }
+    if kill_on_timeout {
+        cu::check!(
+            child.co_kill().await,
+            "failed to kill '{}' after timeout",
+            spawn.executable
+        )?;
+    }
+    cu::bail!(
+        "editor did not exit within {} seconds",
+        timeout.as_secs_f32()
+    );
 }
 
 mod macros {
@@ -124,82 +460,114 @@ mod macros {
         }};
     }
     pub(crate) use configure_and_spawn;
+}
+use macros::*;
 
-    #[cfg(windows)]
-    macro_rules! configure_and_spawn_powershell {
-        (
-            inherit: $inherit:expr,
-            executable: $executable:expr,
-            args: $args:expr,
-            $command:ident => $post:block
-        ) => {{
-            let script = format!(
-                "& \"{}\" {}",
-                $executable,
-                format_powershell_argument_list($args)?
-            );
-            configure_and_spawn! {
-                inherit: $inherit,
-                command: std::path::Path::new("powershell.exe").command().args([
-                    "-NoLogo",
-                    "-NoProfile",
-                    "-Command",
-                    &script
-                ]),
-                $command => $post
-            }
-        }};
-    }
-    #[cfg(windows)]
-    pub(crate) use configure_and_spawn_powershell;
+/// Call the executable with the call operator (`&`) in PowerShell
+#[cfg(windows)]
+fn powershell_call(inherit: bool, executable: &str, args: &[String]) -> SpawnCommand {
+    let script = format!(
+        "& '{}' {}",
+        executable.replace('\'', "''"),
+        format_powershell_argument_list(args)
+    );
+    powershell_command(inherit, script)
+}
 
-    #[cfg(windows)]
-    macro_rules! configure_and_spawn_powershell_for_path_and_wait {
-        (
-            executable: $executable:expr,
-            path: $path:expr,
-            $command:ident => $post:block
-        ) => {{
-            let script = format!(
-                "Start-Process -FilePath \"{}\" -ArgumentList \"{}\" -Wait",
-                $executable, $path
-            );
-            configure_and_spawn! {
-                inherit: false,
-                command: std::path::Path::new("powershell.exe").command().args([
-                    "-NoLogo",
-                    "-NoProfile",
-                    "-Command",
-                    &script
-                ]),
-                $command => $post
-            }
-        }};
+/// Start the executable with `Start-Process` in PowerShell, optionally waiting for it
+#[cfg(windows)]
+fn powershell_start_process(
+    executable: &str,
+    args: &[String],
+    wait: bool,
+) -> cu::Result<SpawnCommand> {
+    let script = format!(
+        "Start-Process -FilePath \"{}\" -ArgumentList {}{}",
+        executable,
+        format_powershell_string_array(args)?,
+        if wait { " -Wait" } else { "" }
+    );
+    Ok(powershell_command(false, script))
+}
+
+/// Open the file with the default application with `Start-Process` in PowerShell
+#[cfg(windows)]
+fn powershell_start_default(path: &str, wait: bool) -> cu::Result<SpawnCommand> {
+    let script = format!(
+        "Start-Process -FilePath '{}'{}",
+        path.replace('\'', "''"),
+        if wait { " -Wait" } else { "" }
+    );
+    Ok(powershell_command(false, script))
+}
+
+#[cfg(windows)]
+fn powershell_command(inherit: bool, script: String) -> SpawnCommand {
+    SpawnCommand {
+        inherit,
+        executable: "powershell.exe".to_string(),
+        args: vec![
+            "-NoLogo".to_string(),
+            "-NoProfile".to_string(),
+            "-Command".to_string(),
+            script,
+        ],
     }
-    #[cfg(windows)]
-    pub(crate) use configure_and_spawn_powershell_for_path_and_wait;
 }
-use macros::*;
 
+/// Format the args for the call operator (`&`) in PowerShell.
+///
+/// Each arg is single-quoted, so whitespace and special characters are kept as-is
+#[cfg(windows)]
+fn format_powershell_argument_list(args: &[String]) -> String {
+    let args = args
+        .iter()
+        .map(|s| format!("'{}'", s.replace('\'', "''")))
+        .collect::<Vec<_>>();
+    args.join(" ")
+}
+
+/// Format the args as a PowerShell string array for `Start-Process -ArgumentList`.
+///
+/// `Start-Process` joins the args with space, so args with whitespace
+/// are quoted with double quotes
 #[cfg(windows)]
-fn format_powershell_argument_list(args: &[String]) -> cu::Result<String> {
-    let mut argument_list = String::new();
-    let mut args_iter = args.iter();
-    if let Some(first) = args_iter.next() {
-        if first.contains(|c: char| c.is_whitespace() || matches!(c, '\'' | '"' | '`' | '$')) {
+fn format_powershell_string_array(args: &[String]) -> cu::Result<String> {
+    let mut array = String::new();
+    for (i, s) in args.iter().enumerate() {
+        if s.contains('"') {
             cu::bail!("the editor command contains illegal characters that cannot be spawned");
         }
-        argument_list.push('"');
-        argument_list.push_str(first);
-        argument_list.push('"');
-        for s in args_iter {
-            if s.contains(|c: char| c.is_whitespace() || matches!(c, '\'' | '"' | '`' | '$')) {
-                cu::bail!("the editor command contains illegal characters that cannot be spawned");
-            }
-            argument_list.push_str(" \"");
-            argument_list.push_str(s);
-            argument_list.push('"');
+        if i != 0 {
+            array.push(',');
+        }
+        array.push('\'');
+        let s = s.replace('\'', "''");
+        if s.contains(char::is_whitespace) {
+            array.push('"');
+            array.push_str(&s);
+            array.push('"');
+        } else {
+            array.push_str(&s);
         }
+        array.push('\'');
+    }
+    Ok(array)
+}
+
+#[cfg(all(test, windows))]
+mod test {
+    use super::*;
+    #[test]
+    fn test_format_powershell_argument_list() {
+        let args = [
+            "-w".to_string(),
+            "C:\\my files\\foo.rs".to_string(),
+            "it's".to_string(),
+        ];
+        assert_eq!(
+            format_powershell_argument_list(&args),
+            "'-w' 'C:\\my files\\foo.rs' 'it''s'"
+        );
     }
-    Ok(argument_list)
 }
@@ -0,0 +1,62 @@
+// SPDX-License-Identifier: MIT
+// Copyright (c) 2026 Pistonite
+
+use std::path::PathBuf;
+
+use cu::pre::*;
+use serde::Deserialize;
+
+/// User config file at `~/.config/viopen/config.toml`
+/// (`%APPDATA%\viopen\config.toml` on Windows)
+#[derive(Debug, Default, Deserialize)]
+pub struct UserConfig {
+    /// The editor command, in the same format as the `EDITOR` environment variable
+    pub editor: Option<String>,
+    /// Override if the editor should inherit the terminal
+    pub inherit: Option<bool>,
+    /// Extra args to pass to the editor before the file paths
+    #[serde(default)]
+    pub args: Vec<String>,
+}
+
+impl UserConfig {
+    /// Load the user config file. Returns `None` if the file does not exist
+    pub fn load() -> cu::Result<Option<Self>> {
+        let Some(path) = config_file_path() else {
+            return Ok(None);
+        };
+        if !path.is_file() {
+            return Ok(None);
+        }
+        cu::trace!("loading user config from '{}'", path.display());
+        let content = cu::check!(
+            std::fs::read_to_string(&path),
+            "failed to read user config '{}'",
+            path.display()
+        )?;
+        let config = cu::check!(
+            toml::from_str(&content),
+            "failed to parse user config '{}'",
+            path.display()
+        )?;
+        Ok(Some(config))
+    }
+}
+
+fn config_file_path() -> Option<PathBuf> {
+    let mut path = if cfg!(windows) {
+        PathBuf::from(cu::env_var("APPDATA").ok()?)
+    } else {
+        match cu::env_var("XDG_CONFIG_HOME") {
+            Ok(x) if !x.is_empty() => PathBuf::from(x),
+            _ => {
+                let mut home = PathBuf::from(cu::env_var("HOME").ok()?);
+                home.push(".config");
+                home
+            }
+        }
+    };
+    path.push("viopen");
+    path.push("config.toml");
+    Some(path)
+}
@@ -2,27 +2,442 @@
 // Copyright (c) 2026 Pistonite
 
 use std::path::Path;
+use std::time::Duration;
 
 mod imp;
 
+/// Options for opening files in the editor
+#[derive(Debug, Default, Clone)]
+pub struct OpenOptions {
+    /// Line to go to (1-based). Overrides the `path:line:col` syntax in the paths
+    pub line: Option<u32>,
+    /// Column to go to (1-based). Only used if `line` is set
+    pub column: Option<u32>,
+    /// Don't wait for the editor to close the file.
+    ///
+    /// This is only possible for GUI editors (like `code`, `subl`, notepad or Notepad++),
+    /// in which case the wait flag is not passed. Terminal editors always block
+    /// until closed.
+    pub no_wait: bool,
+    /// Create the file (and parent directories) if it doesn't exist
+    pub create: bool,
+    /// Print the command that would be spawned to stdout, instead of spawning it
+    pub dry_run: bool,
+    /// Fail if the editor doesn't exit within the timeout.
+    ///
+    /// Only supported by the `co_*` functions, which require the `coroutine` feature.
+    /// The sync functions return an error if this is set.
+    pub timeout: Option<Duration>,
+    /// Kill the editor if it times out. By default, the editor is left running.
+    ///
+    /// On Windows, editors that are `.cmd` scripts (like `code`) are launched through
+    /// PowerShell, and only the PowerShell process is killed, not the editor
+    pub kill_on_timeout: bool,
+    /// Consider GUI editors when searching on Linux even if `DISPLAY` and `WAYLAND_DISPLAY`
+    /// are not set (for example, with X forwarding that doesn't set them)
+    pub force_gui: bool,
+    /// Open in a new window or reuse an existing window, for GUI editors
+    pub window: WindowMode,
+    /// Open at the first match of the pattern, with `+/pattern`.
+    ///
+    /// Only supported by vi family editors and `less`/`more`. For other editors,
+    /// a warning is printed and the pattern is ignored
+    pub search: Option<String>,
+    /// Open in view-only mode with the editor's readonly flag (like `vim -R`).
+    /// A warning is printed if the editor doesn't have one, and the file is opened normally
+    ///
+    /// If no editor is found, the file is viewed in `PAGER` (or `less`/`more`) instead
+    pub readonly: bool,
+    /// Open with the default application (`xdg-open` on Linux, `open` on macOS,
+    /// `start` on Windows) if no editor is found, or if the file is not a text file
+    pub open_with_default: bool,
+}
+
+/// Window behavior for GUI editors, see [`OpenOptions::window`]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum WindowMode {
+    /// Use the editor's own default behavior
+    #[default]
+    Default,
+    /// Open in a new window (like `code -n`)
+    New,
+    /// Reuse the last active window (like `code -r`)
+    Reuse,
+}
+
+/// The editor resolved by viopen, see [`detect_editor`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EditorInfo {
+    /// Absolute path to the editor executable
+    pub executable: String,
+    /// If the editor runs in the terminal and inherits stdio
+    pub inherit: bool,
+    /// Args passed to the editor before the file paths, including the wait flag
+    /// for GUI editors
+    pub args: Vec<String>,
+    /// If the editor supports opening a directory
+    pub supports_directory: bool,
+    /// If the editor supports opening multiple files in one invocation
+    pub supports_multiple_files: bool,
+}
+
+/// Resolve the editor command without spawning it.
+///
+/// If `editor` is empty or cannot be resolved, the user config
+/// and the system are searched, the same way as [`open`]
+#[inline(always)]
+pub fn detect_editor(editor: impl AsRef<str>) -> cu::Result<EditorInfo> {
+    imp::detect_editor(editor.as_ref())
+}
+
+/// Resolve the editor that [`open`] would use, from `VISUAL` or `EDITOR`
+/// and the fallbacks. See [`detect_editor`]
+#[inline(always)]
+pub fn detect_default_editor() -> cu::Result<EditorInfo> {
+    imp::detect_editor(&editor_from_env())
+}
+
+/// Get the editor command from the environment. `VISUAL` takes precedence over `EDITOR`
+fn editor_from_env() -> String {
+    pick_editor(cu::env_var("VISUAL").ok(), cu::env_var("EDITOR").ok())
+}
+
+/// Pick the first non-empty value of `VISUAL` and `EDITOR`
+fn pick_editor(visual: Option<String>, editor: Option<String>) -> String {
+    [visual, editor]
+        .into_iter()
+        .flatten()
+        .find(|x| !x.is_empty())
+        .unwrap_or_default()
+}
+
+/// Open the file in the editor and wait for it to be closed.
+///
+/// The editor is determined in the following order:
+/// - The `VISUAL` environment variable, then `EDITOR`
+/// - The `editor` in the user config file at `~/.config/viopen/config.toml`
+///   (`%APPDATA%\viopen\config.toml` on Windows)
+/// - Searching for common editors on the system. On Linux, GUI editors are skipped
+///   if neither `DISPLAY` nor `WAYLAND_DISPLAY` is set
+///
+/// Extra args in the `VIOPEN_ARGS` environment variable (like `-n` for a new window)
+/// are passed to the editor before the file paths. The wait flag (like `-w` for `code`)
+/// is still added by viopen and not duplicated if `VIOPEN_ARGS` contains it.
+/// With [`OpenOptions::no_wait`], the wait flag is removed even if it's from `VIOPEN_ARGS`
 #[inline(always)]
 pub fn open(path: impl AsRef<Path>) -> cu::Result<()> {
-    imp::open_internal(&cu::env_var("EDITOR").unwrap_or_default(), path.as_ref())
+    imp::open_internal(
+        &editor_from_env(),
+        &[path.as_ref()],
+        &OpenOptions::default(),
+    )
 }
 
 #[inline(always)]
 pub fn open_with(editor: impl AsRef<str>, path: impl AsRef<Path>) -> cu::Result<()> {
-    imp::open_internal(editor.as_ref(), path.as_ref())
+    imp::open_internal(editor.as_ref(), &[path.as_ref()], &OpenOptions::default())
+}
+
+/// Open the file at the line and column (1-based)
+#[inline(always)]
+pub fn open_at(path: impl AsRef<Path>, line: u32, column: Option<u32>) -> cu::Result<()> {
+    imp::open_internal(
+        &editor_from_env(),
+        &[path.as_ref()],
+        &OpenOptions {
+            line: Some(line),
+            column,
+            ..Default::default()
+        },
+    )
+}
+
+/// Open the file at the line and column (1-based) with the editor
+#[inline(always)]
+pub fn open_at_with(
+    editor: impl AsRef<str>,
+    path: impl AsRef<Path>,
+    line: u32,
+    column: Option<u32>,
+) -> cu::Result<()> {
+    imp::open_internal(
+        editor.as_ref(),
+        &[path.as_ref()],
+        &OpenOptions {
+            line: Some(line),
+            column,
+            ..Default::default()
+        },
+    )
+}
+
+/// Open the file without waiting for the editor to close it.
+/// See [`OpenOptions::no_wait`]
+#[inline(always)]
+pub fn open_no_wait(path: impl AsRef<Path>) -> cu::Result<()> {
+    imp::open_internal(
+        &editor_from_env(),
+        &[path.as_ref()],
+        &OpenOptions {
+            no_wait: true,
+            ..Default::default()
+        },
+    )
+}
+
+/// Open the file, creating it (and parent directories) first if it doesn't exist
+#[inline(always)]
+pub fn open_create(path: impl AsRef<Path>) -> cu::Result<()> {
+    imp::open_internal(
+        &editor_from_env(),
+        &[path.as_ref()],
+        &OpenOptions {
+            create: true,
+            ..Default::default()
+        },
+    )
+}
+
+/// Open the file and return the exit code of the editor, instead of ignoring it.
+///
+/// -1 is returned if the editor was terminated by a signal
+#[inline(always)]
+pub fn open_status(path: impl AsRef<Path>) -> cu::Result<i32> {
+    imp::open_status_internal(
+        &editor_from_env(),
+        &[path.as_ref()],
+        &OpenOptions::default(),
+    )
+}
+
+/// Open multiple files with the options and return the exit code of the editor.
+///
+/// If the editor is spawned multiple times, the first nonzero exit code is returned.
+/// See [`open_status`]
+#[inline(always)]
+pub fn open_many_status<P: AsRef<Path>>(paths: &[P], options: &OpenOptions) -> cu::Result<i32> {
+    let paths = paths.iter().map(AsRef::as_ref).collect::<Vec<_>>();
+    imp::open_status_internal(&editor_from_env(), &paths, options)
+}
+
+/// Open multiple files with the editor and options, and return the exit code of the editor.
+/// See [`open_many_status`]
+#[inline(always)]
+pub fn open_many_status_with<P: AsRef<Path>>(
+    editor: impl AsRef<str>,
+    paths: &[P],
+    options: &OpenOptions,
+) -> cu::Result<i32> {
+    let paths = paths.iter().map(AsRef::as_ref).collect::<Vec<_>>();
+    imp::open_status_internal(editor.as_ref(), &paths, options)
+}
+
+/// Open the file in view-only mode. See [`OpenOptions::readonly`]
+#[inline(always)]
+pub fn open_readonly(path: impl AsRef<Path>) -> cu::Result<()> {
+    imp::open_internal(
+        &editor_from_env(),
+        &[path.as_ref()],
+        &OpenOptions {
+            readonly: true,
+            ..Default::default()
+        },
+    )
+}
+
+/// Open multiple files. The files are passed to the editor in one invocation
+/// if the editor supports it, otherwise they are opened one after another
+#[inline(always)]
+pub fn open_many<P: AsRef<Path>>(paths: &[P]) -> cu::Result<()> {
+    let paths = paths.iter().map(AsRef::as_ref).collect::<Vec<_>>();
+    imp::open_internal(&editor_from_env(), &paths, &OpenOptions::default())
+}
+
+/// Open multiple files with the editor. See [`open_many`]
+#[inline(always)]
+pub fn open_many_with<P: AsRef<Path>>(editor: impl AsRef<str>, paths: &[P]) -> cu::Result<()> {
+    let paths = paths.iter().map(AsRef::as_ref).collect::<Vec<_>>();
+    imp::open_internal(editor.as_ref(), &paths, &OpenOptions::default())
+}
+
+/// Open multiple files with the options. See [`open_many`]
+#[inline(always)]
+pub fn open_many_with_options<P: AsRef<Path>>(
+    paths: &[P],
+    options: &OpenOptions,
+) -> cu::Result<()> {
+    let paths = paths.iter().map(AsRef::as_ref).collect::<Vec<_>>();
+    imp::open_internal(&editor_from_env(), &paths, options)
 }
 
 #[inline(always)]
 #[cfg(feature = "coroutine")]
 pub async fn co_open(path: impl AsRef<Path>) -> cu::Result<()> {
-    imp::co_open_internal(&cu::env_var("EDITOR").unwrap_or_default(), path.as_ref()).await
+    imp::co_open_internal(
+        &editor_from_env(),
+        &[path.as_ref()],
+        &OpenOptions::default(),
+    )
+    .await
 }
 
 #[inline(always)]
 #[cfg(feature = "coroutine")]
 pub async fn co_open_with(editor: impl AsRef<str>, path: impl AsRef<Path>) -> cu::Result<()> {
-    imp::co_open_internal(editor.as_ref(), path.as_ref()).await
+    imp::co_open_internal(editor.as_ref(), &[path.as_ref()], &OpenOptions::default()).await
+}
+
+/// Open the file without waiting for the editor to close it.
+/// See [`OpenOptions::no_wait`]
+#[inline(always)]
+#[cfg(feature = "coroutine")]
+pub async fn co_open_no_wait(path: impl AsRef<Path>) -> cu::Result<()> {
+    imp::co_open_internal(
+        &editor_from_env(),
+        &[path.as_ref()],
+        &OpenOptions {
+            no_wait: true,
+            ..Default::default()
+        },
+    )
+    .await
+}
+
+/// Open the file, creating it (and parent directories) first if it doesn't exist
+#[inline(always)]
+#[cfg(feature = "coroutine")]
+pub async fn co_open_create(path: impl AsRef<Path>) -> cu::Result<()> {
+    imp::co_open_internal(
+        &editor_from_env(),
+        &[path.as_ref()],
+        &OpenOptions {
+            create: true,
+            ..Default::default()
+        },
+    )
+    .await
+}
+
+/// Open the file and return the exit code of the editor. See [`open_status`]
+#[inline(always)]
+#[cfg(feature = "coroutine")]
+pub async fn co_open_status(path: impl AsRef<Path>) -> cu::Result<i32> {
+    imp::co_open_status_internal(
+        &editor_from_env(),
+        &[path.as_ref()],
+        &OpenOptions::default(),
+    )
+    .await
+}
+
+/// Open multiple files with the options and return the exit code of the editor.
+/// See [`open_many_status`]
+#[inline(always)]
+#[cfg(feature = "coroutine")]
+pub async fn co_open_many_status<P: AsRef<Path>>(
+    paths: &[P],
+    options: &OpenOptions,
+) -> cu::Result<i32> {
+    let paths = paths.iter().map(AsRef::as_ref).collect::<Vec<_>>();
+    imp::co_open_status_internal(&editor_from_env(), &paths, options).await
+}
+
+/// Open multiple files with the editor and options, and return the exit code of the editor.
+/// See [`open_many_status`]
+#[inline(always)]
+#[cfg(feature = "coroutine")]
+pub async fn co_open_many_status_with<P: AsRef<Path>>(
+    editor: impl AsRef<str>,
+    paths: &[P],
+    options: &OpenOptions,
+) -> cu::Result<i32> {
+    let paths = paths.iter().map(AsRef::as_ref).collect::<Vec<_>>();
+    imp::co_open_status_internal(editor.as_ref(), &paths, options).await
+}
+
+/// Open the file in view-only mode. See [`OpenOptions::readonly`]
+#[inline(always)]
+#[cfg(feature = "coroutine")]
+pub async fn co_open_readonly(path: impl AsRef<Path>) -> cu::Result<()> {
+    imp::co_open_internal(
+        &editor_from_env(),
+        &[path.as_ref()],
+        &OpenOptions {
+            readonly: true,
+            ..Default::default()
+        },
+    )
+    .await
+}
+
+/// Open multiple files. The files are passed to the editor in one invocation
+/// if the editor supports it. Otherwise, GUI editors are spawned concurrently
+/// for each file, and terminal editors are opened one after another
+#[inline(always)]
+#[cfg(feature = "coroutine")]
+pub async fn co_open_many<P: AsRef<Path>>(paths: &[P]) -> cu::Result<()> {
+    let paths = paths.iter().map(AsRef::as_ref).collect::<Vec<_>>();
+    imp::co_open_internal(&editor_from_env(), &paths, &OpenOptions::default()).await
+}
+
+/// Open multiple files with the options. See [`co_open_many`]
+#[inline(always)]
+#[cfg(feature = "coroutine")]
+pub async fn co_open_many_with_options<P: AsRef<Path>>(
+    paths: &[P],
+    options: &OpenOptions,
+) -> cu::Result<()> {
+    let paths = paths.iter().map(AsRef::as_ref).collect::<Vec<_>>();
+    imp::co_open_internal(&editor_from_env(), &paths, options).await
+}
+
+/// Open the file at the line and column (1-based)
+#[inline(always)]
+#[cfg(feature = "coroutine")]
+pub async fn co_open_at(path: impl AsRef<Path>, line: u32, column: Option<u32>) -> cu::Result<()> {
+    imp::co_open_internal(
+        &editor_from_env(),
+        &[path.as_ref()],
+        &OpenOptions {
+            line: Some(line),
+            column,
+            ..Default::default()
+        },
+    )
+    .await
+}
+
+/// Open the file at the line and column (1-based) with the editor
+#[inline(always)]
+#[cfg(feature = "coroutine")]
+pub async fn co_open_at_with(
+    editor: impl AsRef<str>,
+    path: impl AsRef<Path>,
+    line: u32,
+    column: Option<u32>,
+) -> cu::Result<()> {
+    imp::co_open_internal(
+        editor.as_ref(),
+        &[path.as_ref()],
+        &OpenOptions {
+            line: Some(line),
+            column,
+            ..Default::default()
+        },
+    )
+    .await
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    #[test]
+    fn test_pick_editor() {
+        let some = |x: &str| Some(x.to_string());
+        assert_eq!(pick_editor(some("code"), some("vim")), "code");
+        assert_eq!(pick_editor(some(""), some("vim")), "vim");
+        assert_eq!(pick_editor(None, some("vim")), "vim");
+        assert_eq!(pick_editor(some("code"), None), "code");
+        assert_eq!(pick_editor(None, None), "");
+    }
 }
@@ -1,12 +1,251 @@
 // SPDX-License-Identifier: MIT
 // Copyright (c) 2026 Pistonite
 
+use std::io::Read;
+use std::path::PathBuf;
+
 use cu::pre::*;
-fn main() -> cu::Result<()> {
-    let mut args = std::env::args();
-    // executable name
-    let _ = args.next();
-    // only support one file name for now
-    let file = cu::check!(args.next(), "expecting path")?;
-    viopen::open(&file)
+
+/// Open editor and wait for finish
+#[derive(clap::Parser)]
+struct Cli {
+    /// Files to open. Use `path:line:col` or `path:line` to go to a position
+    ///
+    /// Use `-` to read stdin into a temporary file and open it.
+    /// A trailing `+/pattern` is the same as `--search pattern`
+    #[clap(required = true)]
+    files: Vec<String>,
+    /// Editor command to use instead of VISUAL or EDITOR, like `code -n`
+    #[clap(short, long)]
+    editor: Option<String>,
+    /// Line to go to (1-based). Overrides the position in the paths
+    #[clap(short, long)]
+    line: Option<u32>,
+    /// Column to go to (1-based). Requires --line
+    #[clap(long, requires = "line")]
+    col: Option<u32>,
+    /// Open at the first match of the pattern (vi family editors and less only)
+    #[clap(short, long)]
+    search: Option<String>,
+    /// Don't wait for GUI editors to close the file
+    #[clap(long)]
+    no_wait: bool,
+    /// Create the files (and parent directories) if they don't exist
+    #[clap(short, long)]
+    create: bool,
+    /// Print the editor command instead of spawning it
+    #[clap(long)]
+    dry_run: bool,
+    /// Print the temporary file for `-` to stdout after the editor is closed
+    #[clap(short, long)]
+    print: bool,
+    /// Consider GUI editors even if DISPLAY and WAYLAND_DISPLAY are not set
+    #[clap(long)]
+    gui: bool,
+    /// Open in a new window, for GUI editors
+    #[clap(long, conflicts_with = "reuse")]
+    new_window: bool,
+    /// Reuse the last active window, for GUI editors
+    #[clap(long)]
+    reuse: bool,
+    /// Open in view-only mode, if the editor supports it
+    #[clap(long)]
+    readonly: bool,
+    /// Open with the default application if no editor is found or the file is not a text file
+    #[clap(long)]
+    open_with_default: bool,
+    /// Keep the temporary file for `-` instead of deleting it
+    #[clap(short, long)]
+    keep: bool,
+    /// Fail if the editor doesn't exit within this many seconds
+    #[cfg(feature = "coroutine")]
+    #[clap(long)]
+    timeout: Option<f32>,
+    /// Kill the editor on timeout, instead of leaving it running.
+    /// On Windows, `.cmd` editors (like `code`) are launched through PowerShell,
+    /// and only the PowerShell process is killed
+    #[cfg(feature = "coroutine")]
+    #[clap(long, requires = "timeout")]
+    kill_on_timeout: bool,
+    #[clap(flatten)]
+    flags: cu::cli::Flags,
+}
+
+#[cfg(not(feature = "coroutine"))]
+#[cu::cli(flags = "flags")]
+fn main(cli: Cli) -> cu::Result<()> {
+    let run = Run::new(cli)?;
+    let result = match &run.editor {
+        Some(editor) => viopen::open_many_status_with(editor, &run.files, &run.options),
+        None => viopen::open_many_status(&run.files, &run.options),
+    };
+    run.finish(result)
+}
+
+#[cfg(feature = "coroutine")]
+#[cu::cli(flags = "flags")]
+async fn main(cli: Cli) -> cu::Result<()> {
+    let run = Run::new(cli)?;
+    let result = match &run.editor {
+        Some(editor) => viopen::co_open_many_status_with(editor, &run.files, &run.options).await,
+        None => viopen::co_open_many_status(&run.files, &run.options).await,
+    };
+    run.finish(result)
+}
+
+struct Run {
+    /// The editor from --editor
+    editor: Option<String>,
+    files: Vec<String>,
+    options: viopen::OpenOptions,
+    /// The temporary file if reading from stdin
+    stdin_file: Option<PathBuf>,
+    print: bool,
+    keep: bool,
+}
+
+impl Run {
+    fn new(mut cli: Cli) -> cu::Result<Self> {
+        if cli.files.len() > 1
+            && let Some(pattern) = cli.files.last().and_then(|x| x.strip_prefix("+/"))
+        {
+            if cli.search.is_some() {
+                cu::bail!("cannot use both --search and +/pattern");
+            }
+            cli.search = Some(pattern.to_string());
+            cli.files.pop();
+        }
+        let stdin_index = cli.files.iter().position(|x| x == "-");
+        let stdin_file = match stdin_index {
+            Some(i) => {
+                let path = cu::check!(read_stdin_to_temp_file(), "failed to read stdin")?;
+                cli.files[i] = path.as_utf8()?.to_string();
+                Some(path)
+            }
+            None => None,
+        };
+        if stdin_file.is_some() && cli.no_wait && cli.print {
+            cu::warn!("--print with --no-wait will print the content before editing");
+        }
+        #[allow(unused_mut)]
+        let mut options = viopen::OpenOptions {
+            line: cli.line,
+            column: cli.col,
+            no_wait: cli.no_wait,
+            create: cli.create,
+            dry_run: cli.dry_run,
+            force_gui: cli.gui,
+            window: if cli.new_window {
+                viopen::WindowMode::New
+            } else if cli.reuse {
+                viopen::WindowMode::Reuse
+            } else {
+                viopen::WindowMode::Default
+            },
+            readonly: cli.readonly,
+            search: cli.search,
+            open_with_default: cli.open_with_default,
+            ..Default::default()
+        };
+        #[cfg(feature = "coroutine")]
+        {
+            options.timeout = cli
+                .timeout
+                .map(|x| {
+                    cu::check!(
+                        std::time::Duration::try_from_secs_f32(x),
+                        "invalid --timeout: {x}"
+                    )
+                })
+                .transpose()?;
+            options.kill_on_timeout = cli.kill_on_timeout;
+        }
+        Ok(Self {
+            editor: cli.editor,
+            files: cli.files,
+            options,
+            stdin_file,
+            print: cli.print,
+            keep: cli.keep,
+        })
+    }
+
+    /// Clean up, and exit with the editor's exit code if it's nonzero
+    fn finish(self, result: cu::Result<i32>) -> cu::Result<()> {
+        let Some(path) = self.stdin_file else {
+            return exit_with(result);
+        };
+        if result.is_ok() && self.print {
+            let content = cu::check!(
+                std::fs::read_to_string(&path),
+                "failed to read temporary file '{}'",
+                path.display()
+            )?;
+            print!("{content}");
+        }
+        if self.keep {
+            cu::info!("temporary file kept at '{}'", path.display());
+        } else if let Err(e) = std::fs::remove_file(&path) {
+            cu::warn!(
+                "failed to remove temporary file '{}': {e:?}",
+                path.display()
+            );
+        }
+        exit_with(result)
+    }
 }
+
+/// Exit with the editor's exit code if it's nonzero
+fn exit_with(result: cu::Result<i32>) -> cu::Result<()> {
+    match result? {
+        0 => Ok(()),
+        code => {
+            cu::debug!("editor exited with code {code}");
+            std::process::exit(code)
+        }
+    }
+}
+
+/// Read stdin into a new temporary file, and return the path to it
+fn read_stdin_to_temp_file() -> cu::Result<PathBuf> {
+    let mut content = Vec::new();
+    std::io::stdin().read_to_end(&mut content)?;
+    let timestamp = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos();
+    let mut path = std::env::temp_dir();
+    path.push(format!("viopen-{}-{timestamp}.txt", std::process::id()));
+    cu::check!(
+        std::fs::write(&path, content),
+        "failed to write temporary file '{}'",
+        path.display()
+    )?;
+    cu::debug!("stdin written to '{}'", path.display());
+    reopen_tty_as_stdin();
+    Ok(path)
+}
+
+/// Terminal editors need the terminal as stdin, which is now
+/// the exhausted pipe. Reopen the terminal as stdin if possible
+#[cfg(unix)]
+fn reopen_tty_as_stdin() {
+    use std::os::fd::AsRawFd;
+    let tty = match std::fs::File::open("/dev/tty") {
+        Ok(x) => x,
+        Err(e) => {
+            cu::debug!("failed to open /dev/tty: {e:?}");
+            return;
+        }
+    };
+    // SAFETY: both file descriptors are valid
+    if unsafe { libc::dup2(tty.as_raw_fd(), libc::STDIN_FILENO) } == -1 {
+        cu::debug!(
+            "failed to reopen /dev/tty as stdin: {:?}",
+            std::io::Error::last_os_error()
+        );
+    }
+}
+
+#[cfg(not(unix))]
+fn reopen_tty_as_stdin() {}
@@ -0,0 +1,164 @@
+// SPDX-License-Identifier: MIT
+// Copyright (c) 2026 Pistonite
+
+//! Platform-independent parts of editing PATH in a text file
+
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+
+use cu::pre::*;
+
+/// Open the file in the editor, and wait for it to be closed.
+/// If the editor is not specified, it's detected by viopen (`$VISUAL`, `$EDITOR`, etc.)
+pub fn edit_file(path: &Path, editor: Option<&str>) -> cu::Result<()> {
+    let result = match editor {
+        Some(editor) => viopen::open_with(editor, path),
+        None => viopen::open(path),
+    };
+    cu::check!(result, "unable to open temporary file in editor")
+}
+
+/// Parse the edited file into the paths of each section, joined with the separator.
+///
+/// A line `@NAME` starts the section `NAME`, and paths before any section marker
+/// go into the first section. Each line can have one path, or multiple paths
+/// separated by the separator. Duplicates in the same section are removed
+pub fn parse_sections(content: &str, sections: &[&str], separator: char) -> Vec<String> {
+    let mut paths = vec![vec![]; sections.len()];
+    let mut current = 0;
+    for line in content.lines() {
+        let line = strip_missing_annotation(line).trim();
+        if line.starts_with('#') {
+            continue;
+        }
+        if line.is_empty() {
+            continue;
+        }
+        if let Some(i) = line
+            .strip_prefix('@')
+            .and_then(|name| sections.iter().position(|x| *x == name))
+        {
+            current = i;
+            continue;
+        }
+        let paths = &mut paths[current];
+        for p in line.split(separator) {
+            let p = p.trim();
+            if !paths.contains(&p) {
+                paths.push(p);
+            }
+        }
+    }
+    paths
+        .into_iter()
+        .map(|x| x.join(&separator.to_string()))
+        .collect()
+}
+
+/// Strip the annotation added by [`format_paths`].
+///
+/// Only the annotation at the end is stripped, since `#` can be part of a path
+fn strip_missing_annotation(line: &str) -> &str {
+    let line = line.trim_end();
+    line.strip_suffix(MISSING_ANNOTATION).unwrap_or(line)
+}
+
+/// Format the paths one per line, optionally annotating directories that don't exist
+pub fn format_paths(paths: &[&str], check_missing: bool) -> String {
+    paths
+        .iter()
+        .map(|x| {
+            if check_missing && is_missing(x) {
+                format!("{x}  {MISSING_ANNOTATION}")
+            } else {
+                x.to_string()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Annotation for directories in PATH that don't exist
+const MISSING_ANNOTATION: &str = "# (missing)";
+
+/// Count the paths that are not existing directories
+pub fn count_missing(value: &str, separator: char) -> usize {
+    clean_path(value, separator, false)
+        .into_iter()
+        .filter(|x| is_missing(x))
+        .count()
+}
+
+/// Check if the path is not an existing directory, after expanding `%VAR%` on Windows
+pub fn is_missing(path: &str) -> bool {
+    #[cfg(windows)]
+    let path = win_envedit::expand(path);
+    !Path::new(&path).is_dir()
+}
+
+/// Split the value into paths, removing empty paths and duplicates,
+/// and optionally sorting them case-insensitively
+pub fn clean_path(x: &str, separator: char, sort: bool) -> Vec<&str> {
+    let mut seen = BTreeSet::new();
+    let mut out = vec![];
+    for s in x.split(separator) {
+        let s = s.trim();
+        if s.is_empty() {
+            continue;
+        }
+        if !seen.insert(s) {
+            continue;
+        }
+        out.push(s);
+    }
+    if sort {
+        out.sort_by_key(|x| x.to_lowercase());
+    }
+    out
+}
+
+/// The temporary file for editing the variable, next to the executable
+pub fn temp_file_path(var: &str) -> cu::Result<PathBuf> {
+    let mut parent = cu::fs::current_exe()?.parent_abs()?;
+    if var.eq_ignore_ascii_case("PATH") {
+        parent.push("vipath.temp");
+    } else {
+        parent.push(format!("vipath.{}.temp", var.to_lowercase()));
+    }
+    Ok(parent)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    #[test]
+    fn test_clean_path_keeps_first() {
+        assert_eq!(
+            clean_path("/b:/a: /c :/a::/b", ':', false),
+            vec!["/b", "/a", "/c"]
+        );
+        assert_eq!(clean_path("/b:/A:/c", ':', true), vec!["/A", "/b", "/c"]);
+    }
+
+    #[test]
+    fn test_parse_sections_keeps_order() {
+        let content = "/z\n/y:/z\n# /w\n/x  # (missing)\n@B\n/b\n@A\n/a:/z";
+        assert_eq!(
+            parse_sections(content, &["A", "B"], ':'),
+            vec!["/z:/y:/x:/a", "/b"]
+        );
+    }
+
+    #[test]
+    fn test_parse_sections_keeps_hash_in_path() {
+        let paths = [
+            "/not/exist/foo #2/bin",
+            "C:\\Not Exist\\C# Tools",
+            "/not/exist/a # b",
+        ];
+        for check_missing in [false, true] {
+            let content = format_paths(&paths, check_missing);
+            assert_eq!(parse_sections(&content, &["A"], ';'), vec![paths.join(";")]);
+        }
+    }
+}
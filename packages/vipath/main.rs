@@ -1,12 +1,20 @@
 // SPDX-License-Identifier: MIT
 // Copyright (c) 2026 Pistonite
 
+mod common;
 #[cfg(not(windows))]
-compile_error!("this package can only be installed on windows");
+mod main_unix;
 #[cfg(windows)]
 mod main_win;
+
 #[cfg(windows)]
 #[cu::cli(flags = "flags")]
 fn main(cli: main_win::Cli) -> cu::Result<()> {
     main_win::run(cli)
 }
+
+#[cfg(not(windows))]
+#[cu::cli(flags = "flags")]
+fn main(cli: main_unix::Cli) -> cu::Result<()> {
+    main_unix::run(cli)
+}
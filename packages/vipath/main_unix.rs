@@ -0,0 +1,393 @@
+// SPDX-License-Identifier: MIT
+// Copyright (c) 2026 Pistonite
+
+use std::path::{Path, PathBuf};
+
+use cu::pre::*;
+
+use crate::common::{self, clean_path};
+
+static BEGIN_MARKER: &str = "# >>> vipath managed PATH >>>";
+static END_MARKER: &str = "# <<< vipath managed PATH <<<";
+
+#[derive(clap::Parser)]
+pub struct Cli {
+    /// Check, don't edit
+    #[clap(short, long)]
+    pub check: bool,
+    /// The editor to use. Default is `$VISUAL`, `$EDITOR`, or a detected editor
+    #[clap(short, long)]
+    pub editor: Option<String>,
+    /// The shell profile to write PATH to. Default is based on $SHELL
+    /// (~/.bashrc, ~/.zshrc, or ~/.profile)
+    #[clap(long)]
+    pub profile: Option<PathBuf>,
+    #[clap(flatten)]
+    pub flags: cu::cli::Flags,
+}
+
+pub fn run(cli: Cli) -> cu::Result<()> {
+    cu::lv::disable_print_time();
+    let profile = match cli.profile {
+        Some(profile) => profile,
+        None => cu::check!(default_profile(), "failed to determine shell profile")?,
+    };
+    let current_paths = std::env::var("PATH").unwrap_or_default();
+    if cli.check {
+        report_missing(&current_paths);
+        match read_managed_path(&profile)? {
+            Some(managed) if !managed.is_applied(&current_paths) => {
+                cu::warn!(
+                    "changes to PATH in '{}' are not in the current PATH, restart the shell or run `source {}`",
+                    profile.display(),
+                    profile.display()
+                );
+            }
+            Some(_) => {}
+            None => cu::info!("no PATH managed by vipath in '{}'", profile.display()),
+        }
+        cu::info!("OK");
+        return Ok(());
+    }
+
+    let path = cu::check!(
+        common::temp_file_path("PATH"),
+        "failed to determine temporary file path"
+    )?;
+    let content = make_path_file(&profile, &current_paths);
+    cu::check!(
+        cu::fs::write(&path, content),
+        "failed to write PATH to temporary file"
+    )?;
+
+    common::edit_file(&path, cli.editor.as_deref())?;
+    cu::check!(
+        apply_file(&path, &profile, &current_paths),
+        "failed to apply temporary file"
+    )?;
+    cu::fs::remove(&path)?;
+    Ok(())
+}
+
+fn apply_file(path: &Path, profile: &Path, current_paths: &str) -> cu::Result<bool> {
+    let content = cu::fs::read_string(path)?;
+    let (profile, paths) = parse_path_file(&content, profile);
+    if paths == current_paths {
+        cu::info!("PATH is not changed");
+        return Ok(false);
+    }
+    report_missing(&paths);
+    cu::debug!("applying path={paths}");
+    let managed = read_managed_path(&profile)?
+        .unwrap_or_default()
+        .update(current_paths, &paths);
+    cu::check!(
+        write_managed_path(&profile, &managed),
+        "failed to write PATH to '{}'",
+        profile.display()
+    )?;
+    cu::warn!(
+        "PATH is updated in '{}', restart the shell or run `source {}`",
+        profile.display(),
+        profile.display()
+    );
+    Ok(true)
+}
+
+/// Warn about PATH entries that don't exist
+fn report_missing(paths: &str) {
+    let count = common::count_missing(paths, ':');
+    if count > 0 {
+        cu::warn!("{count} PATH entries do not exist");
+    }
+}
+
+/// Parse path file into the profile to write to and the PATH
+fn parse_path_file(content: &str, profile: &Path) -> (PathBuf, String) {
+    let mut profile = profile.to_path_buf();
+    let mut lines = vec![];
+    for line in content.lines() {
+        match line.trim().strip_prefix("@PROFILE") {
+            Some(x) if !x.trim().is_empty() => profile = PathBuf::from(x.trim()),
+            _ => lines.push(line),
+        }
+    }
+    let paths = common::parse_sections(&lines.join("\n"), &["PATH"], ':')
+        .into_iter()
+        .next()
+        .unwrap_or_default();
+    (profile, paths)
+}
+
+fn make_path_file(profile: &Path, current_paths: &str) -> String {
+    format!(
+        r#"
+# Temporary file for editing PATH
+# Put one path per line, or multiple in the same line separated by :
+# Lines starting with # will be ignored
+# Directories that don't exist are marked with # (missing)
+# Duplicates will be removed
+#
+# The changes will be written to the shell profile below, in a block managed by vipath,
+# and applied to PATH when the shell starts. Change it to write to a different profile
+@PROFILE {}
+
+# -------------------------------
+@PATH
+# -------------------------------
+{}
+
+    "#,
+        profile.display(),
+        common::format_paths(&clean_path(current_paths, ':', false), true),
+    )
+}
+
+fn default_profile() -> cu::Result<PathBuf> {
+    let home = PathBuf::from(cu::env_var("HOME")?);
+    let shell = std::env::var("SHELL").unwrap_or_default();
+    let file = match shell.rsplit('/').next() {
+        Some("bash") => ".bashrc",
+        Some("zsh") => ".zshrc",
+        _ => ".profile",
+    };
+    Ok(home.join(file))
+}
+
+/// Read the changes in the managed block of the profile, if any
+fn read_managed_path(profile: &Path) -> cu::Result<Option<ManagedPath>> {
+    if !profile.exists() {
+        return Ok(None);
+    }
+    let content = cu::fs::read_string(profile)?;
+    let Some((start, end)) = find_managed_block(&content) else {
+        return Ok(None);
+    };
+    Ok(Some(ManagedPath::parse(&content[start..end])))
+}
+
+/// Write the changes to the managed block of the profile
+fn write_managed_path(profile: &Path, managed: &ManagedPath) -> cu::Result<()> {
+    let content = if profile.exists() {
+        cu::fs::read_string(profile)?
+    } else {
+        String::new()
+    };
+    let block = format!("{BEGIN_MARKER}\n{}{END_MARKER}", managed.to_script());
+    cu::fs::write(profile, replace_managed_block(&content, &block))
+}
+
+/// Replace the existing managed block with the new block, or append it
+fn replace_managed_block(content: &str, block: &str) -> String {
+    match find_managed_block(content) {
+        Some((start, end)) => format!("{}{block}{}", &content[..start], &content[end..]),
+        None if content.is_empty() || content.ends_with('\n') => format!("{content}\n{block}\n"),
+        None => format!("{content}\n\n{block}\n"),
+    }
+}
+
+/// Find the byte range of the managed block, including the markers
+fn find_managed_block(content: &str) -> Option<(usize, usize)> {
+    let start = content.find(BEGIN_MARKER)?;
+    let end = content[start..].find(END_MARKER)? + start + END_MARKER.len();
+    Some((start, end))
+}
+
+/// Changes to PATH in the managed block, relative to PATH before the block runs.
+///
+/// Only the changes are written, so PATH set up by the system or by other tools
+/// (for example, virtual environments) is not overwritten
+#[derive(Debug, Default, PartialEq)]
+struct ManagedPath {
+    /// Paths to add before PATH
+    prepend: Vec<String>,
+    /// Paths to add after PATH
+    append: Vec<String>,
+    /// Paths to remove from PATH
+    remove: Vec<String>,
+}
+
+/// Script in the managed block after the variables. The paths to add are removed
+/// from PATH first, so they are not duplicated and running it again doesn't change PATH
+static MANAGED_SCRIPT: &str = r#"__vipath_rest="$PATH:"
+__vipath_path=
+while [ -n "$__vipath_rest" ]; do
+    __vipath_p="${__vipath_rest%%:*}"
+    __vipath_rest="${__vipath_rest#*:}"
+    case ":$__vipath_prepend:$__vipath_append:$__vipath_remove:" in
+        *":$__vipath_p:"*) ;;
+        *) __vipath_path="${__vipath_path:+$__vipath_path:}$__vipath_p" ;;
+    esac
+done
+if [ -n "$__vipath_prepend" ]; then __vipath_path="$__vipath_prepend${__vipath_path:+:$__vipath_path}"; fi
+if [ -n "$__vipath_append" ]; then __vipath_path="${__vipath_path:+$__vipath_path:}$__vipath_append"; fi
+export PATH="$__vipath_path"
+unset __vipath_prepend __vipath_append __vipath_remove __vipath_rest __vipath_path __vipath_p
+"#;
+
+impl ManagedPath {
+    /// Parse the variables in the managed block
+    fn parse(block: &str) -> Self {
+        let mut out = Self::default();
+        for line in block.lines() {
+            let Some((name, value)) = line.trim().split_once('=') else {
+                continue;
+            };
+            let list = match name {
+                "__vipath_prepend" => &mut out.prepend,
+                "__vipath_append" => &mut out.append,
+                "__vipath_remove" => &mut out.remove,
+                _ => continue,
+            };
+            let value = unquote(value);
+            *list = clean_path(&value, ':', false)
+                .into_iter()
+                .map(String::from)
+                .collect();
+        }
+        out
+    }
+
+    fn to_script(&self) -> String {
+        format!(
+            "__vipath_prepend={}\n__vipath_append={}\n__vipath_remove={}\n{MANAGED_SCRIPT}",
+            quote(&self.prepend.join(":")),
+            quote(&self.append.join(":")),
+            quote(&self.remove.join(":")),
+        )
+    }
+
+    /// Check if the changes are in the current PATH, i.e. the shell has run the block
+    fn is_applied(&self, current: &str) -> bool {
+        let current = clean_path(current, ':', false);
+        self.prepend
+            .iter()
+            .chain(&self.append)
+            .all(|x| current.contains(&x.as_str()))
+            && !self.remove.iter().any(|x| current.contains(&x.as_str()))
+    }
+
+    /// Compute the changes to turn `current` (which already has these changes)
+    /// into `new`, keeping the previous removals of paths that are still not in `new`
+    fn update(&self, current: &str, new: &str) -> Self {
+        // PATH before the block runs, except the paths removed by the block
+        let base = clean_path(current, ':', false)
+            .into_iter()
+            .filter(|x| !self.prepend.iter().chain(&self.append).any(|y| y == x))
+            .collect::<Vec<_>>();
+        let new = clean_path(new, ':', false);
+        // keep the longest run of paths that are in the same order as the base,
+        // everything before or after it is added
+        let (mut start, mut end) = (0, 0);
+        for i in 0..new.len() {
+            let mut last = None;
+            let mut j = i;
+            while j < new.len() {
+                let Some(index) = base.iter().position(|x| *x == new[j]) else {
+                    break;
+                };
+                if last.is_some_and(|x| index <= x) {
+                    break;
+                }
+                last = Some(index);
+                j += 1;
+            }
+            if j - i > end - start {
+                (start, end) = (i, j);
+            }
+        }
+        let mut remove = vec![];
+        for x in base
+            .iter()
+            .copied()
+            .chain(self.remove.iter().map(String::as_str))
+        {
+            if !new.contains(&x) && !remove.contains(&x) {
+                remove.push(x);
+            }
+        }
+        let to_owned = |x: &[&str]| x.iter().map(|x| x.to_string()).collect();
+        Self {
+            prepend: to_owned(&new[..start]),
+            append: to_owned(&new[end..]),
+            remove: to_owned(&remove),
+        }
+    }
+}
+
+/// Quote the value for POSIX shells
+fn quote(x: &str) -> String {
+    format!("'{}'", x.replace('\'', r"'\''"))
+}
+
+fn unquote(x: &str) -> String {
+    match x.strip_prefix('\'').and_then(|x| x.strip_suffix('\'')) {
+        Some(x) => x.replace(r"'\''", "'"),
+        None => x.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    #[test]
+    fn test_quote() {
+        assert_eq!(quote("/a:/b"), "'/a:/b'");
+        assert_eq!(quote("it's"), r"'it'\''s'");
+        assert_eq!(unquote(&quote("it's")), "it's");
+        assert_eq!(unquote("/a"), "/a");
+    }
+
+    #[test]
+    fn test_replace_managed_block() {
+        let block = format!("{BEGIN_MARKER}\nfoo\n{END_MARKER}");
+        assert_eq!(replace_managed_block("", &block), format!("\n{block}\n"));
+        assert_eq!(
+            replace_managed_block("a\n", &block),
+            format!("a\n\n{block}\n")
+        );
+        assert_eq!(
+            replace_managed_block("a", &block),
+            format!("a\n\n{block}\n")
+        );
+        let content = format!("a\n{BEGIN_MARKER}\nold\n{END_MARKER}\nb\n");
+        assert_eq!(
+            replace_managed_block(&content, &block),
+            format!("a\n{block}\nb\n")
+        );
+        // not closed
+        let content = format!("a\n{BEGIN_MARKER}\nb\n");
+        assert_eq!(find_managed_block(&content), None);
+    }
+
+    #[test]
+    fn test_managed_path_update() {
+        let managed = ManagedPath {
+            prepend: vec!["/p".to_string()],
+            append: vec![],
+            remove: vec!["/r".to_string(), "/s".to_string()],
+        };
+        let updated = managed.update("/p:/a:/b:/c:/d", "/x:/a:/c:/d:/y:/s");
+        assert_eq!(
+            updated,
+            ManagedPath {
+                prepend: vec!["/x".to_string()],
+                append: vec!["/y".to_string(), "/s".to_string()],
+                remove: vec!["/b".to_string(), "/r".to_string()],
+            }
+        );
+        assert!(updated.is_applied("/x:/a:/c:/d:/y:/s"));
+        assert!(!updated.is_applied("/x:/a:/b:/c:/d:/y:/s"));
+        assert_eq!(ManagedPath::parse(&updated.to_script()), updated);
+        // moving a path to the front
+        let updated = ManagedPath::default().update("/a:/b:/c", "/c:/a:/b");
+        assert_eq!(
+            updated,
+            ManagedPath {
+                prepend: vec!["/c".to_string()],
+                append: vec![],
+                remove: vec![],
+            }
+        );
+    }
+}
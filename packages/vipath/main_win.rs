@@ -1,120 +1,560 @@
 // SPDX-License-Identifier: MIT
 // Copyright (c) 2026 Pistonite
 
-use std::collections::BTreeSet;
 use std::path::{Path, PathBuf};
 
 use cu::pre::*;
 
+use crate::common::{self, clean_path};
+
 #[derive(clap::Parser)]
 pub struct Cli {
     /// Check, don't edit
     #[clap(short, long)]
     pub check: bool,
+    /// The editor to use. Default is `$VISUAL`, `$EDITOR`, or a detected editor
+    #[clap(short, long)]
+    pub editor: Option<String>,
+    /// Remove paths in USER that are already in SYSTEM, instead of only warning.
+    /// SYSTEM paths take precedence, so the USER copy has no effect
+    #[clap(short, long)]
+    pub dedup: bool,
+    /// Show what would be changed after editing, without applying.
+    /// The temporary file is kept, run `vipath -c` to apply it
+    #[clap(long)]
+    pub diff: bool,
+    /// Sort the paths in each section case-insensitively, when editing and when applying.
+    /// Note that this changes which executable is found first if multiple paths have it
+    #[clap(long)]
+    pub sort: bool,
+    /// The `;`-delimited environment variable to edit
+    #[clap(long, default_value = "PATH")]
+    pub var: String,
+    /// Append the directory to USER PATH without editing, if it's not already in it
+    #[clap(long, value_name = "DIR", conflicts_with = "var")]
+    pub add_user: Vec<String>,
+    /// Append the directory to SYSTEM PATH without editing, if it's not already in it
+    #[clap(long, value_name = "DIR", conflicts_with = "var")]
+    pub add_system: Vec<String>,
+    /// Remove the directory from USER PATH without editing.
+    /// Matching is case-insensitive and ignores trailing slashes
+    #[clap(long, value_name = "DIR", conflicts_with = "var")]
+    pub remove_user: Vec<String>,
+    /// Remove the directory from SYSTEM PATH without editing.
+    /// Matching is case-insensitive and ignores trailing slashes
+    #[clap(long, value_name = "DIR", conflicts_with = "var")]
+    pub remove_system: Vec<String>,
+    /// Restore from a backup file made before applying changes,
+    /// or the most recent backup if no file is given
+    #[clap(long, value_name = "FILE", num_args = 0..=1)]
+    pub restore: Option<Option<PathBuf>>,
+    /// Write the current values to the file in the same format as the temporary file, without editing
+    #[clap(long, value_name = "FILE", conflicts_with = "import")]
+    pub export: Option<PathBuf>,
+    /// Apply a file previously written with --export, without editing
+    #[clap(long, value_name = "FILE")]
+    pub import: Option<PathBuf>,
+    /// Print the current SYSTEM and USER values as JSON, with whether each path is missing
+    #[clap(long)]
+    pub json: bool,
     #[clap(flatten)]
     pub flags: cu::cli::Flags,
 }
 
 pub fn run(cli: Cli) -> cu::Result<()> {
     cu::lv::disable_print_time();
-    let path = cu::check!(temp_file_path(), "failed to determine temporary file path")?;
+    if cli.sort {
+        cu::warn!(
+            "--sort changes the order of {}, which changes the precedence of the entries",
+            cli.var
+        );
+    }
+    if let Some(file) = &cli.restore {
+        return restore(file.as_deref());
+    }
+    if cli.json {
+        let current_system_paths = win_envedit::get_system(&cli.var)?;
+        let current_user_paths = win_envedit::get_user(&cli.var)?;
+        println!(
+            "{{\"system\":{},\"user\":{}}}",
+            paths_to_json(&current_system_paths),
+            paths_to_json(&current_user_paths)
+        );
+        return Ok(());
+    }
+    if let Some(file) = &cli.export {
+        let content = cu::check!(
+            parse_env(&cli),
+            "failed to parse {} environment variables",
+            cli.var
+        )?;
+        cu::check!(
+            cu::fs::write(file, content),
+            "failed to export {} to '{}'",
+            cli.var,
+            file.display()
+        )?;
+        cu::info!("exported {} to '{}'", cli.var, file.display());
+        return Ok(());
+    }
+    if let Some(file) = &cli.import {
+        let applied = cu::check!(
+            apply_file(file, &cli, false),
+            "failed to import '{}'",
+            file.display()
+        )?;
+        if !applied {
+            cu::info!("{} is the same as '{}'", cli.var, file.display());
+        }
+        return Ok(());
+    }
+    if !cli.add_user.is_empty()
+        || !cli.add_system.is_empty()
+        || !cli.remove_user.is_empty()
+        || !cli.remove_system.is_empty()
+    {
+        return update_paths(&cli);
+    }
+    let path = cu::check!(
+        common::temp_file_path(&cli.var),
+        "failed to determine temporary file path"
+    )?;
     // clean up previous temp file
     if path.is_file() {
-        let applied = cu::check!(apply_file(&path), "failed to apply previous temporary file")?;
+        if cli.diff {
+            cu::check!(
+                diff_file(&path, &cli),
+                "failed to diff previous temporary file"
+            )?;
+            return Ok(());
+        }
+        let applied = cu::check!(
+            apply_file(&path, &cli, false),
+            "failed to apply previous temporary file"
+        )?;
+        cu::fs::remove(&path)?;
         if applied {
-            cu::error!("please restart the terminal process and run `vipath -c`");
+            cu::error!(
+                "please restart the terminal process and run `vipath -c{}`",
+                var_arg(&cli.var)
+            );
             return Ok(());
-        } else {
-            cu::fs::remove(&path)?;
         }
     }
     if cli.check {
+        let current_system_paths = win_envedit::get_system(&cli.var)?;
+        let current_user_paths = win_envedit::get_user(&cli.var)?;
+        report_missing(&cli.var, &current_system_paths, &current_user_paths);
         cu::info!("OK");
         return Ok(());
     }
 
-    let content = cu::check!(parse_env(), "failed to parse PATH environment variables")?;
+    let content = cu::check!(
+        parse_env(&cli),
+        "failed to parse {} environment variables",
+        cli.var
+    )?;
     cu::check!(
         cu::fs::write(&path, content),
-        "failed to write PATH to temporary file"
+        "failed to write {} to temporary file",
+        cli.var
     )?;
 
+    common::edit_file(&path, cli.editor.as_deref())?;
+    if cli.diff {
+        cu::check!(diff_file(&path, &cli), "failed to diff temporary file")?;
+        return Ok(());
+    }
     cu::check!(
-        viopen::open(&path),
-        "unable to open temporary file in editor"
+        apply_file(&path, &cli, true),
+        "failed to apply temporary file"
     )?;
-    cu::check!(apply_file(&path), "failed to apply temporary file")?;
+    // the file is kept if applying failed, so it's applied again on the next run.
+    // Otherwise, remove it so it's not applied again over later changes
+    if path.is_file() {
+        cu::fs::remove(&path)?;
+    }
     Ok(())
 }
 
-fn apply_file(path: &Path) -> cu::Result<bool> {
-    let content = cu::fs::read_string(path)?;
-    let (system_paths, user_paths) = parse_path_file(&content)?;
+/// Apply the edited file. If `confirm`, show the changes and prompt before applying
+/// (skipped with `--yes`), and remove the file if not confirmed
+fn apply_file(path: &Path, cli: &Cli, confirm: bool) -> cu::Result<bool> {
+    let (system_paths, user_paths) = read_file(path, cli)?;
 
-    let current_system_paths = win_envedit::get_system("PATH")?;
-    let current_user_paths = win_envedit::get_user("PATH")?;
+    let var = &cli.var;
+    let current_system_paths = win_envedit::get_system(var)?;
+    let current_user_paths = win_envedit::get_user(var)?;
+    if system_paths == current_system_paths && user_paths == current_user_paths {
+        return Ok(false);
+    }
+    report_missing(var, &system_paths, &user_paths);
+    if confirm {
+        print_diff("SYSTEM", &current_system_paths, &system_paths);
+        print_diff("USER", &current_user_paths, &user_paths);
+        if !cu::yesno!("apply the changes to {var}?")? {
+            cu::info!("changes are not applied");
+            cu::fs::remove(path)?;
+            return Ok(false);
+        }
+    }
+    let backup = cu::check!(
+        backup_path(var, &current_system_paths, &current_user_paths),
+        "failed to back up current {var}"
+    )?;
+    cu::info!(
+        "current {var} is backed up to '{}', undo with `vipath --restore`",
+        backup.display()
+    );
     let mut applied = false;
     if system_paths != current_system_paths {
         cu::debug!("applying system={system_paths}");
-        win_envedit::set_system("PATH", &system_paths)?;
+        win_envedit::set_system(var, &system_paths)?;
         applied = true;
     }
     if user_paths != current_user_paths {
         cu::debug!("applying user={user_paths}");
-        win_envedit::set_user("PATH", &user_paths)?;
+        win_envedit::set_user(var, &user_paths)?;
         applied = true;
     }
     if applied {
-        cu::warn!("PATH is updated, restart the terminal process and run `vipath -c`");
+        notify_updated(var);
     }
     Ok(applied)
 }
 
-/// Parse path file into SYSTEM and USER paths
-fn parse_path_file(content: &str) -> cu::Result<(String, String)> {
-    let mut system_paths = vec![];
-    let mut user_paths = vec![];
-    let mut is_system = true;
-    for line in content.lines() {
-        let line = line.trim();
-        if line.starts_with('#') {
+/// Append or remove directories in PATH without editing
+fn update_paths(cli: &Cli) -> cu::Result<()> {
+    let mut changed = false;
+    for dir in &cli.remove_system {
+        if win_envedit::remove_system_path(dir)? {
+            cu::info!("removed '{dir}' from SYSTEM PATH");
+            changed = true;
+        } else {
+            cu::info!("'{dir}' is not in SYSTEM PATH");
+        }
+    }
+    for dir in &cli.remove_user {
+        if win_envedit::remove_user_path(dir)? {
+            cu::info!("removed '{dir}' from USER PATH");
+            changed = true;
+        } else {
+            cu::info!("'{dir}' is not in USER PATH");
+        }
+    }
+    for dir in &cli.add_system {
+        if win_envedit::append_system_path(dir)? {
+            cu::info!("added '{dir}' to SYSTEM PATH");
+            changed = true;
+        } else {
+            cu::info!("'{dir}' is already in SYSTEM PATH");
+        }
+    }
+    for dir in &cli.add_user {
+        if win_envedit::append_user_path(dir)? {
+            cu::info!("added '{dir}' to USER PATH");
+            changed = true;
+        } else {
+            cu::info!("'{dir}' is already in USER PATH");
+        }
+    }
+    if changed {
+        notify_updated("PATH");
+    }
+    Ok(())
+}
+
+/// Broadcast the change and tell the user how to pick it up
+fn notify_updated(var: &str) {
+    // notify running processes like Explorer, so new terminals get the updated value
+    match win_envedit::broadcast_environment_change() {
+        Ok(()) => {
+            cu::warn!(
+                "{var} is updated for new processes, but the current terminal still has the old {var}; open a new terminal to use it"
+            );
+        }
+        Err(e) => {
+            cu::debug!("{e:?}");
+            cu::warn!(
+                "{var} is updated, restart the terminal process and run `vipath -c{}`",
+                var_arg(var)
+            );
+        }
+    }
+}
+
+/// Show the paths that would be added or removed by applying the file
+fn diff_file(path: &Path, cli: &Cli) -> cu::Result<()> {
+    let (system_paths, user_paths) = read_file(path, cli)?;
+    let current_system_paths = win_envedit::get_system(&cli.var)?;
+    let current_user_paths = win_envedit::get_user(&cli.var)?;
+    let changed = print_diff("SYSTEM", &current_system_paths, &system_paths)
+        | print_diff("USER", &current_user_paths, &user_paths);
+    if changed {
+        cu::info!(
+            "nothing is applied, run `vipath -c{}` to apply the changes",
+            var_arg(&cli.var)
+        );
+    } else {
+        cu::info!("no changes");
+    }
+    Ok(())
+}
+
+/// Print the removed and added paths in the section, return if anything changed
+fn print_diff(section: &str, old: &str, new: &str) -> bool {
+    let old = clean_path(old, ';', false);
+    let new = clean_path(new, ';', false);
+    let mut changed = false;
+    for p in old.iter().filter(|x| !new.contains(x)) {
+        println!("{section} - {p}");
+        changed = true;
+    }
+    for p in new.iter().filter(|x| !old.contains(x)) {
+        println!("{section} + {p}");
+        changed = true;
+    }
+    if !changed && old != new {
+        println!("{section} (reordered)");
+        changed = true;
+    }
+    changed
+}
+
+/// Read and parse the edited file
+fn read_file(path: &Path, cli: &Cli) -> cu::Result<(String, String)> {
+    let content = cu::fs::read_string(path)?;
+    let (system_paths, user_paths) = parse_path_file(&content)?;
+    let user_paths = dedup_user_paths(&system_paths, &user_paths, cli.dedup);
+    if cli.sort {
+        let system_paths = clean_path(&system_paths, ';', true).join(";");
+        let user_paths = clean_path(&user_paths, ';', true).join(";");
+        return Ok((system_paths, user_paths));
+    }
+    Ok((system_paths, user_paths))
+}
+
+/// Find paths in USER that are also in SYSTEM. Warn about them, or remove them if `remove`
+fn dedup_user_paths(system_paths: &str, user_paths: &str, remove: bool) -> String {
+    let system = system_paths
+        .split(';')
+        .map(normalize_path)
+        .collect::<Vec<_>>();
+    let mut kept = vec![];
+    for p in user_paths.split(';') {
+        if p.trim().is_empty() || !system.contains(&normalize_path(p)) {
+            kept.push(p);
             continue;
         }
-        if line.is_empty() {
+        if remove {
+            cu::info!("removed '{p}' from USER, it's already in SYSTEM");
+        } else {
+            cu::warn!("'{p}' is in both SYSTEM and USER, use --dedup to remove it from USER");
+            kept.push(p);
+        }
+    }
+    kept.join(";")
+}
+
+/// Normalize the path for comparison. Paths on Windows are case-insensitive,
+/// and trailing slashes don't matter
+fn normalize_path(p: &str) -> String {
+    p.trim().trim_end_matches(['\\', '/']).to_lowercase()
+}
+
+/// Warn about PATH entries that don't exist. Other variables are not checked
+fn report_missing(var: &str, system_paths: &str, user_paths: &str) {
+    if !is_path(var) {
+        return;
+    }
+    let count = common::count_missing(system_paths, ';') + common::count_missing(user_paths, ';');
+    if count > 0 {
+        cu::warn!("{count} PATH entries do not exist");
+    }
+}
+
+/// Format the paths as a JSON array of `{"path": string, "missing": bool}`
+fn paths_to_json(paths: &str) -> String {
+    let entries = clean_path(paths, ';', false)
+        .into_iter()
+        .map(|x| {
+            format!(
+                "{{\"path\":\"{}\",\"missing\":{}}}",
+                json_escape(x),
+                common::is_missing(x)
+            )
+        })
+        .collect::<Vec<_>>();
+    format!("[{}]", entries.join(","))
+}
+
+fn json_escape(x: &str) -> String {
+    let mut out = String::with_capacity(x.len());
+    for c in x.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            c if c.is_control() => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// If the variable is PATH, which has directories that can be checked for existence
+fn is_path(var: &str) -> bool {
+    var.eq_ignore_ascii_case("PATH")
+}
+
+/// The `--var` argument to show in messages
+fn var_arg(var: &str) -> String {
+    if is_path(var) {
+        String::new()
+    } else {
+        format!(" --var {var}")
+    }
+}
+
+/// Save the current SYSTEM and USER values to a timestamped file next to the temporary file
+fn backup_path(var: &str, system_paths: &str, user_paths: &str) -> cu::Result<PathBuf> {
+    let timestamp = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|x| x.as_millis())
+        .unwrap_or_default();
+    let mut path = common::temp_file_path(var)?;
+    path.set_file_name(format!("vipath-backup-{timestamp}.txt"));
+    let content = format!(
+        "# backup before editing with vipath\nVAR={var}\nSYSTEM={system_paths}\nUSER={user_paths}\n"
+    );
+    cu::fs::write(&path, content)?;
+    Ok(path)
+}
+
+/// Restore both SYSTEM and USER values from the backup file, after confirming the changes
+fn restore(file: Option<&Path>) -> cu::Result<()> {
+    let path = match file {
+        Some(file) => file.to_path_buf(),
+        None => cu::check!(latest_backup(), "failed to find the most recent backup")?,
+    };
+    let content = cu::fs::read_string(&path)?;
+    let (var, system_paths, user_paths) = cu::check!(
+        parse_backup(&content),
+        "failed to parse backup file '{}'",
+        path.display()
+    )?;
+    let current_system_paths = win_envedit::get_system(&var)?;
+    let current_user_paths = win_envedit::get_user(&var)?;
+    if system_paths == current_system_paths && user_paths == current_user_paths {
+        cu::info!("{var} is the same as the backup");
+        return Ok(());
+    }
+    print_diff("SYSTEM", &current_system_paths, &system_paths);
+    print_diff("USER", &current_user_paths, &user_paths);
+    if !cu::yesno!("restore {var} from '{}'?", path.display())? {
+        cu::info!("not restored");
+        return Ok(());
+    }
+    win_envedit::set_system(&var, &system_paths)?;
+    if let Err(e) = win_envedit::set_user(&var, &user_paths) {
+        // put SYSTEM back, so it's either fully restored or not at all
+        if let Err(e) = win_envedit::set_system(&var, &current_system_paths) {
+            cu::error!("failed to undo restoring SYSTEM {var}: {e:?}");
+        }
+        cu::rethrow!(e, "failed to restore USER {var}");
+    }
+    cu::info!("restored {var} from '{}'", path.display());
+    notify_updated(&var);
+    Ok(())
+}
+
+/// Find the most recent backup file next to the temporary file
+fn latest_backup() -> cu::Result<PathBuf> {
+    let dir = common::temp_file_path("PATH")?.parent_abs()?;
+    let mut latest: Option<(u128, PathBuf)> = None;
+    for entry in cu::check!(
+        std::fs::read_dir(&dir),
+        "failed to read '{}'",
+        dir.display()
+    )? {
+        let entry = cu::check!(entry, "failed to read '{}'", dir.display())?;
+        let name = entry.file_name();
+        let Some(timestamp) = name
+            .to_str()
+            .and_then(|x| x.strip_prefix("vipath-backup-"))
+            .and_then(|x| x.strip_suffix(".txt"))
+            .and_then(|x| x.parse::<u128>().ok())
+        else {
             continue;
+        };
+        if latest.as_ref().is_none_or(|(x, _)| *x < timestamp) {
+            latest = Some((timestamp, entry.path()));
+        }
+    }
+    match latest {
+        Some((_, path)) => Ok(path),
+        None => cu::bail!("no backup found in '{}'", dir.display()),
+    }
+}
+
+/// Parse the backup file into the variable name, SYSTEM and USER values
+fn parse_backup(content: &str) -> cu::Result<(String, String, String)> {
+    let mut var = None;
+    let mut system_paths = None;
+    let mut user_paths = None;
+    for line in content.lines() {
+        if let Some(x) = line.strip_prefix("VAR=") {
+            var = Some(x.to_string());
+        } else if let Some(x) = line.strip_prefix("SYSTEM=") {
+            system_paths = Some(x.to_string());
+        } else if let Some(x) = line.strip_prefix("USER=") {
+            user_paths = Some(x.to_string());
         }
-        match line {
-            "@SYSTEM" => is_system = true,
-            "@USER" => is_system = false,
-            _ => {
-                let paths = if is_system {
-                    &mut system_paths
-                } else {
-                    &mut user_paths
-                };
-                for p in line.split(';') {
-                    let p = p.trim();
-                    if !paths.contains(&p) {
-                        paths.push(p);
-                    }
-                }
-            }
-        }
-    }
-    Ok((system_paths.join(";"), user_paths.join(";")))
-}
-
-fn parse_env() -> cu::Result<String> {
-    let current_system_paths = win_envedit::get_system("PATH")?;
-    let current_user_paths = win_envedit::get_user("PATH")?;
+    }
+    let (Some(system_paths), Some(user_paths)) = (system_paths, user_paths) else {
+        cu::bail!("missing SYSTEM or USER value");
+    };
+    let var = var.unwrap_or_else(|| "PATH".to_string());
+    Ok((var, system_paths, user_paths))
+}
+
+/// Parse path file into SYSTEM and USER paths
+fn parse_path_file(content: &str) -> cu::Result<(String, String)> {
+    let mut sections = common::parse_sections(content, &["SYSTEM", "USER"], ';').into_iter();
+    let system_paths = sections.next().unwrap_or_default();
+    let user_paths = sections.next().unwrap_or_default();
+    Ok((system_paths, user_paths))
+}
+
+fn parse_env(cli: &Cli) -> cu::Result<String> {
+    let var = &cli.var;
+    let current_system_paths = win_envedit::get_system(var)?;
+    let current_user_paths = win_envedit::get_user(var)?;
     cu::debug!("current system={current_system_paths}");
     cu::debug!("current user={current_user_paths}");
-    let out = format!(
+    Ok(format_path_file(
+        var,
+        &current_system_paths,
+        &current_user_paths,
+        cli.sort,
+        is_path(var),
+    ))
+}
+
+/// Format the SYSTEM and USER values into the file for editing
+fn format_path_file(
+    var: &str,
+    system_paths: &str,
+    user_paths: &str,
+    sort: bool,
+    check_missing: bool,
+) -> String {
+    format!(
         r#"
-# Temporary file for editing PATH
+# Temporary file for editing {var}
 # Put one path per line, or multiple in the same line separated by ;
 # Lines starting with # will be ignored
-# @SYSTEM and @USER marks sections for SYSTEM path and USER path
+# Directories that don't exist are marked with # (missing)
+# @SYSTEM and @USER marks sections for SYSTEM {var} and USER {var}
 # Duplicates will be removed
 
 # -------------------------------
@@ -129,31 +569,34 @@ fn parse_env() -> cu::Result<String> {
 {}
 
     "#,
-        clean_path(&current_system_paths).join("\n"),
-        clean_path(&current_user_paths).join("\n"),
-    );
-
-    Ok(out)
+        common::format_paths(&clean_path(system_paths, ';', sort), check_missing),
+        common::format_paths(&clean_path(user_paths, ';', sort), check_missing),
+    )
 }
 
-fn clean_path(x: &str) -> Vec<&str> {
-    let mut seen = BTreeSet::new();
-    let mut out = vec![];
-    for s in x.split(';') {
-        let s = s.trim();
-        if s.is_empty() {
-            continue;
-        }
-        if !seen.insert(s) {
-            continue;
-        }
-        out.push(s);
+#[cfg(test)]
+mod test {
+    use super::*;
+    #[test]
+    fn test_round_trip_keeps_order() -> cu::Result<()> {
+        let system = r"C:\b;C:\a;C:\c;C:\a;C:\b";
+        let user = r"D:\z;;D:\y;D:\z;D:\x";
+        let content = format_path_file("PATH", system, user, false, false);
+        let (system, user) = parse_path_file(&content)?;
+        assert_eq!(system, r"C:\b;C:\a;C:\c");
+        assert_eq!(user, r"D:\z;D:\y;D:\x");
+        Ok(())
     }
-    out
-}
 
-fn temp_file_path() -> cu::Result<PathBuf> {
-    let mut parent = cu::fs::current_exe()?.parent_abs()?;
-    parent.push("vipath.temp");
-    Ok(parent)
+    #[test]
+    fn test_round_trip_unchanged() -> cu::Result<()> {
+        let system = r"C:\Windows\system32;C:\Windows;%SystemRoot%\System32\Wbem";
+        let user = r"%USERPROFILE%\.cargo\bin;C:\Tools";
+        let content = format_path_file("PATH", system, user, false, false);
+        assert_eq!(
+            parse_path_file(&content)?,
+            (system.to_string(), user.to_string())
+        );
+        Ok(())
+    }
 }
@@ -1,10 +1,13 @@
 // SPDX-License-Identifier: MIT
 // Copyright (c) 2026 Pistonite
 
-use std::path::PathBuf;
+use std::collections::BTreeSet;
+use std::io::IsTerminal;
+use std::path::{Component, Path, PathBuf};
 use std::process::ExitCode;
 
-use clap::Parser;
+use clap::builder::styling::{AnsiColor, Style};
+use clap::{ColorChoice, Parser};
 
 /// which - shows the full patah of (shell) commands
 #[derive(Debug, Clone, Parser)]
@@ -12,41 +15,290 @@ struct Cli {
     /// Name of the program to expand
     pub programname: String,
     /// Get all matches
-    #[clap(short, long)]
+    #[clap(short, long, conflicts_with = "first")]
     pub all: bool,
+    /// Only get the first match (the default). Searching PATH stops at the first hit
+    #[clap(short = '1', long)]
+    pub first: bool,
+    /// Note if the program is a common shell builtin instead of searching PATH
+    #[clap(long)]
+    pub note_builtins: bool,
+    /// Print the path relative to this directory instead of absolute
+    ///
+    /// The absolute path is printed if there is no relative path
+    /// (e.g. on a different drive on Windows)
+    #[clap(long)]
+    pub relative_to: Option<PathBuf>,
+    /// When to color the output. `NO_COLOR` is respected in auto mode
+    #[clap(long, default_value = "auto")]
+    pub color: ColorChoice,
+    /// Print the type of each match (`file`, `symlink` or `dir`) before the path
+    #[clap(short = 't', long = "type")]
+    pub show_type: bool,
+    /// Don't remove matches that resolve to the same target with --all
+    #[clap(long)]
+    pub no_dedup: bool,
 }
 
+/// Common POSIX shell builtins
+static SHELL_BUILTINS: &[&str] = &[
+    ".", ":", "alias", "bg", "break", "cd", "command", "continue", "echo", "eval", "exec", "exit",
+    "export", "false", "fc", "fg", "getopts", "hash", "jobs", "kill", "printf", "pwd", "read",
+    "readonly", "return", "set", "shift", "test", "times", "trap", "true", "type", "ulimit",
+    "umask", "unalias", "unset", "wait",
+];
+
 fn main() -> ExitCode {
     let cli = Cli::parse();
-    if cli.all {
-        match which::which_all_global(&cli.programname) {
-            Ok(x) => x.for_each(print_path),
-            Err(e) => return print_error(&cli.programname, e),
-        }
-    } else {
-        match which::which_global(&cli.programname) {
-            Ok(path) => print_path(path),
-            Err(e) => return print_error(&cli.programname, e),
+    if cli.note_builtins && SHELL_BUILTINS.contains(&cli.programname.as_str()) {
+        println!("{}: shell builtin", cli.programname);
+        return ExitCode::FAILURE;
+    }
+    let relative_to = match cli.relative_to.as_deref().map(std::path::absolute) {
+        None => None,
+        Some(Ok(x)) => Some(x),
+        Some(Err(e)) => {
+            eprintln!("which: failed to resolve --relative-to: {e}");
+            return ExitCode::FAILURE;
         }
+    };
+    let printer = Printer {
+        relative_to,
+        show_type: cli.show_type,
+        stdout_color: use_color(cli.color, std::io::stdout().is_terminal()),
+        stderr_color: use_color(cli.color, std::io::stderr().is_terminal()),
+    };
+    let matches = which::which_all_global(&cli.programname)
+        .map(|x| select_matches(x, cli.all, !cli.no_dedup))
+        .and_then(|x| match x.is_empty() {
+            true => Err(which::Error::CannotFindBinaryPath),
+            false => Ok(x),
+        });
+    match matches {
+        Ok(x) => x.into_iter().for_each(|path| printer.print_path(path)),
+        Err(e) => return printer.print_error(&cli.programname, e),
     }
 
     ExitCode::SUCCESS
 }
 
-fn print_error(programname: &str, e: which::Error) -> ExitCode {
-    let paths = std::env::var("PATH").unwrap_or_default();
+/// Select the matches to print from the lazy PATH search.
+///
+/// If `all` is false, only the first match is taken and the rest
+/// of PATH is not searched.
+fn select_matches(matches: impl Iterator<Item = PathBuf>, all: bool, dedup: bool) -> Vec<PathBuf> {
+    if !all {
+        return matches.take(1).collect();
+    }
+    if !dedup {
+        return matches.collect();
+    }
+    let mut seen = BTreeSet::new();
+    matches
+        .filter(|path| {
+            // the same target can be found through different
+            // PATH entries, symlinks, or PATHEXT
+            let target = path.canonicalize().unwrap_or_else(|_| path.clone());
+            seen.insert(target)
+        })
+        .collect()
+}
+
+fn use_color(choice: ColorChoice, is_terminal: bool) -> bool {
+    match choice {
+        ColorChoice::Always => true,
+        ColorChoice::Never => false,
+        ColorChoice::Auto => {
+            is_terminal && std::env::var_os("NO_COLOR").is_none_or(|x| x.is_empty())
+        }
+    }
+}
+
+struct Printer {
+    relative_to: Option<PathBuf>,
+    show_type: bool,
+    stdout_color: bool,
+    stderr_color: bool,
+}
+
+impl Printer {
+    fn print_error(&self, programname: &str, e: which::Error) -> ExitCode {
+        let paths = std::env::var("PATH").unwrap_or_default();
+
+        let mut error_string = format!(": {e}");
+        // clear the error string for the most common error for same output
+        // as unix
+        if error_string == ": cannot find binary path" {
+            error_string.clear()
+        }
+
+        let message = format!("which: no {programname} in ({paths}){error_string}");
+        if self.stderr_color {
+            let style = Style::new().fg_color(Some(AnsiColor::Red.into()));
+            eprintln!("{style}{message}{style:#}");
+        } else {
+            eprintln!("{message}");
+        }
+        ExitCode::FAILURE
+    }
+
+    fn print_path(&self, path: PathBuf) {
+        let type_prefix = if self.show_type {
+            format!("{} ", path_type(&path))
+        } else {
+            String::new()
+        };
+        let path = match self
+            .relative_to
+            .as_deref()
+            .and_then(|base| relative_path(&path, base))
+        {
+            Some(x) => x,
+            None => path,
+        };
+        if !self.stdout_color {
+            println!("{type_prefix}{}", path.display());
+            return;
+        }
+        // highlight the binary name
+        let style = Style::new().bold().fg_color(Some(AnsiColor::Green.into()));
+        let path = path.display().to_string();
+        let name_len = Path::new(&path)
+            .file_name()
+            .map(|x| x.to_string_lossy().len())
+            .unwrap_or_default();
+        let (dir, name) = path.split_at(path.len() - name_len);
+        println!("{type_prefix}{dir}{style}{name}{style:#}");
+    }
+}
+
+/// Classify the match based on its metadata, without following symlinks
+fn path_type(path: &Path) -> &'static str {
+    match path.symlink_metadata() {
+        Ok(x) if x.is_symlink() => "symlink",
+        Ok(x) if x.is_dir() => "dir",
+        _ => "file",
+    }
+}
 
-    let mut error_string = format!(": {e}");
-    // clear the error string for the most common error for same output
-    // as unix
-    if error_string == ": cannot find binary path" {
-        error_string.clear()
+/// Compute `path` relative to `base`. Both should be absolute.
+///
+/// Returns `None` if there is no relative path between the 2 (i.e. different prefix)
+fn relative_path(path: &Path, base: &Path) -> Option<PathBuf> {
+    let path = normalize(path);
+    let base = normalize(base);
+    let mut path_iter = path.components().peekable();
+    let mut base_iter = base.components().peekable();
+    // prefix and root must match
+    loop {
+        match (path_iter.peek(), base_iter.peek()) {
+            (Some(a @ (Component::Prefix(_) | Component::RootDir)), Some(b))
+                if component_eq(a, b) =>
+            {
+                path_iter.next();
+                base_iter.next();
+            }
+            (Some(Component::Prefix(_) | Component::RootDir), _)
+            | (_, Some(Component::Prefix(_) | Component::RootDir)) => return None,
+            _ => break,
+        }
     }
+    // skip common components
+    while let (Some(a), Some(b)) = (path_iter.peek(), base_iter.peek()) {
+        if !component_eq(a, b) {
+            break;
+        }
+        path_iter.next();
+        base_iter.next();
+    }
+    let mut out = PathBuf::new();
+    for _ in base_iter {
+        out.push("..");
+    }
+    out.extend(path_iter);
+    if out.as_os_str().is_empty() {
+        out.push(".");
+    }
+    Some(out)
+}
 
-    eprintln!("which: no {programname} in ({paths}){error_string}");
-    ExitCode::FAILURE
+/// Paths are case-insensitive on Windows
+fn component_eq(a: &Component, b: &Component) -> bool {
+    if cfg!(windows) {
+        a.as_os_str().eq_ignore_ascii_case(b.as_os_str())
+    } else {
+        a == b
+    }
+}
+
+/// Resolve `.` and `..` in the absolute path, without accessing the file system.
+///
+/// `canonicalize` is not used, since on Windows it returns verbatim paths (`\\?\C:\...`),
+/// which don't share the prefix with paths found in PATH
+fn normalize(path: &Path) -> PathBuf {
+    let mut out = PathBuf::new();
+    for c in path.components() {
+        match c {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                out.pop();
+            }
+            c => out.push(c),
+        }
+    }
+    out
 }
 
-fn print_path(path: PathBuf) {
-    println!("{}", path.display())
+#[cfg(test)]
+mod test {
+    use super::*;
+    #[test]
+    fn test_select_first_stops_search() {
+        let matches = ["/a/foo", "/b/foo"].into_iter().map(|x| {
+            if x == "/b/foo" {
+                panic!("should not search past the first match");
+            }
+            PathBuf::from(x)
+        });
+        assert_eq!(
+            select_matches(matches, false, true),
+            vec![PathBuf::from("/a/foo")]
+        );
+    }
+    #[test]
+    fn test_select_all_dedup() {
+        let matches = ["/a/foo", "/b/foo", "/a/foo"]
+            .into_iter()
+            .map(PathBuf::from);
+        assert_eq!(
+            select_matches(matches.clone(), true, true),
+            vec![PathBuf::from("/a/foo"), PathBuf::from("/b/foo")]
+        );
+        assert_eq!(select_matches(matches, true, false).len(), 3);
+    }
+    #[test]
+    fn test_relative_path() {
+        let relative = |path: &str, base: &str| relative_path(Path::new(path), Path::new(base));
+        let some = |x: &str| Some(PathBuf::from(x));
+        assert_eq!(relative("/a/b/foo", "/a/b"), some("foo"));
+        assert_eq!(relative("/a/b/foo", "/a"), some("b/foo"));
+        assert_eq!(relative("/a/b/foo", "/a/b/foo"), some("."));
+        // sibling
+        assert_eq!(relative("/a/b/foo", "/a/c"), some("../b/foo"));
+        // parent
+        assert_eq!(relative("/a/foo", "/a/b/c"), some("../../foo"));
+        // base with ..
+        assert_eq!(relative("/a/b/foo", "/a/c/.."), some("b/foo"));
+        assert_eq!(relative("/a/foo", "/a/b/../c/./d"), some("../../foo"));
+        assert_eq!(relative("/a/foo", "/.."), some("a/foo"));
+        // different root
+        assert_eq!(relative("/a/foo", "a"), None);
+        #[cfg(windows)]
+        {
+            assert_eq!(relative("C:\\a\\foo", "D:\\a"), None);
+            // case-insensitive
+            assert_eq!(relative("C:\\A\\Foo\\bar", "c:\\a\\foo"), some("bar"));
+            assert_eq!(relative("C:\\a\\foo", "c:\\A\\B"), some("..\\foo"));
+        }
+    }
 }
@@ -1,15 +1,109 @@
 // SPDX-License-Identifier: MIT
 // Copyright (c) 2026 Pistonite
 
+use std::collections::BTreeMap;
 use std::io::ErrorKind;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 
 use cu::pre::*;
-use winreg::enums::{HKEY_CURRENT_USER, HKEY_LOCAL_MACHINE, KEY_WRITE};
-use winreg::{HKEY, RegKey};
+use winreg::enums::{KEY_READ, KEY_WRITE, REG_EXPAND_SZ, REG_MULTI_SZ, REG_SZ, RegType};
+use winreg::types::{FromRegValue, ToRegValue};
+use winreg::{RegKey, RegValue};
+
+mod backup;
+pub use backup::*;
+mod export;
+pub use export::*;
+mod watch;
+pub use watch::*;
+
+pub use winreg::HKEY;
+pub use winreg::enums::{HKEY_CURRENT_USER, HKEY_LOCAL_MACHINE, HKEY_USERS};
 
 static SYSTEM_PATH: &str = "SYSTEM\\CurrentControlSet\\Control\\Session Manager\\Environment";
 static USER_PATH: &str = "Environment";
 
+/// If the change should be broadcasted after every write
+static AUTO_BROADCAST: AtomicBool = AtomicBool::new(false);
+
+/// Practical maximum length (in UTF-16 units) of a value, to use with [`set_max_value_len`].
+///
+/// Values longer than 2047 characters are known to break some tools
+/// (for example, `setx` and older versions of Explorer truncate `PATH`)
+pub const PRACTICAL_MAX_VALUE_LEN: usize = 2047;
+
+/// Maximum length of a value that can be written, `0` means no limit
+static MAX_VALUE_LEN: AtomicUsize = AtomicUsize::new(0);
+
+/// Set the maximum length (in UTF-16 units) of a value that the setters will write,
+/// for example [`PRACTICAL_MAX_VALUE_LEN`]. `0` disables the check, which is the default.
+///
+/// Note that Windows has a hard limit of 32767 characters for an environment variable
+pub fn set_max_value_len(len: usize) {
+    MAX_VALUE_LEN.store(len, Ordering::Relaxed);
+}
+
+/// Error if the value is longer than the limit set with [`set_max_value_len`]
+fn check_value_len(name: &str, value: &str) -> cu::Result<()> {
+    let max = MAX_VALUE_LEN.load(Ordering::Relaxed);
+    if max == 0 {
+        return Ok(());
+    }
+    let len = value.encode_utf16().count();
+    if len > max {
+        cu::bail!("value of '{name}' is too long ({len} > {max}), see set_max_value_len");
+    }
+    Ok(())
+}
+
+/// Call [`broadcast_environment_change`] automatically after setting or deleting
+/// a variable. This is off by default
+pub fn set_auto_broadcast(enabled: bool) {
+    AUTO_BROADCAST.store(enabled, Ordering::Relaxed);
+}
+
+/// Notify running processes (like Explorer) that the environment has changed,
+/// so newly launched processes pick up the change without logging out.
+///
+/// Processes that are already running still need to be restarted to see the change
+pub fn broadcast_environment_change() -> cu::Result<()> {
+    use windows_sys::Win32::UI::WindowsAndMessaging::{
+        HWND_BROADCAST, SMTO_ABORTIFHUNG, SendMessageTimeoutW, WM_SETTINGCHANGE,
+    };
+    let param = "Environment\0".encode_utf16().collect::<Vec<u16>>();
+    let mut result = 0;
+    // SAFETY: param is a null-terminated wide string that lives through the call
+    let ret = unsafe {
+        SendMessageTimeoutW(
+            HWND_BROADCAST,
+            WM_SETTINGCHANGE,
+            0,
+            param.as_ptr() as isize,
+            SMTO_ABORTIFHUNG,
+            5000,
+            &mut result,
+        )
+    };
+    if ret == 0 {
+        cu::rethrow!(
+            std::io::Error::last_os_error(),
+            "failed to broadcast environment change"
+        );
+    }
+    Ok(())
+}
+
+/// Broadcast the change if auto broadcast is on. Failure to broadcast is only a warning
+/// since the value is already written
+fn after_write() {
+    if !AUTO_BROADCAST.load(Ordering::Relaxed) {
+        return;
+    }
+    if let Err(e) = broadcast_environment_change() {
+        cu::warn!("{e:?}");
+    }
+}
+
 /// Get system environment variable. Not set is returned as empty
 pub fn get_system(key: &str) -> cu::Result<String> {
     cu::check!(
@@ -26,19 +120,629 @@ pub fn get_user(key: &str) -> cu::Result<String> {
     )
 }
 
+/// Check if the system environment variable is set, even if it's set to empty
+pub fn exists_system(key: &str) -> cu::Result<bool> {
+    let raw = cu::check!(
+        get_raw_from_key_path(key, HKEY_LOCAL_MACHINE, SYSTEM_PATH),
+        "failed to check system environment variable '{key}'"
+    )?;
+    Ok(raw.is_some())
+}
+
+/// Check if the user environment variable is set, even if it's set to empty
+pub fn exists_user(key: &str) -> cu::Result<bool> {
+    let raw = cu::check!(
+        get_raw_from_key_path(key, HKEY_CURRENT_USER, USER_PATH),
+        "failed to check user environment variable '{key}'"
+    )?;
+    Ok(raw.is_some())
+}
+
+/// Registry type of an environment variable
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EnvVarType {
+    /// The variable is not set
+    NotSet,
+    /// `REG_SZ`, a plain string
+    Sz,
+    /// `REG_EXPAND_SZ`, a string with `%VAR%` references that are expanded
+    ExpandSz,
+    /// `REG_MULTI_SZ`, a list of strings
+    MultiSz,
+    /// Other registry types, which are not normally used for environment variables
+    Other,
+}
+
+impl EnvVarType {
+    /// Name of the type used in files
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::NotSet => "not_set",
+            Self::Sz => "sz",
+            Self::ExpandSz => "expand_sz",
+            Self::MultiSz => "multi_sz",
+            Self::Other => "other",
+        }
+    }
+
+    /// Parse the name from [`as_str`](Self::as_str)
+    fn parse(s: &str) -> Option<Self> {
+        [
+            Self::NotSet,
+            Self::Sz,
+            Self::ExpandSz,
+            Self::MultiSz,
+            Self::Other,
+        ]
+        .into_iter()
+        .find(|x| x.as_str() == s)
+    }
+}
+
+impl From<RegType> for EnvVarType {
+    fn from(vtype: RegType) -> Self {
+        match vtype {
+            REG_SZ => Self::Sz,
+            REG_EXPAND_SZ => Self::ExpandSz,
+            REG_MULTI_SZ => Self::MultiSz,
+            _ => Self::Other,
+        }
+    }
+}
+
+/// Get the registry type of the system environment variable
+pub fn get_system_type(key: &str) -> cu::Result<EnvVarType> {
+    let raw = cu::check!(
+        get_raw_from_key_path(key, HKEY_LOCAL_MACHINE, SYSTEM_PATH),
+        "failed to get type of system environment variable '{key}'"
+    )?;
+    Ok(raw.map_or(EnvVarType::NotSet, |x| x.vtype.into()))
+}
+
+/// Get the registry type of the user environment variable
+pub fn get_user_type(key: &str) -> cu::Result<EnvVarType> {
+    let raw = cu::check!(
+        get_raw_from_key_path(key, HKEY_CURRENT_USER, USER_PATH),
+        "failed to get type of user environment variable '{key}'"
+    )?;
+    Ok(raw.map_or(EnvVarType::NotSet, |x| x.vtype.into()))
+}
+
+/// Get the value and type from any registry location, like `Volatile Environment`
+/// under `HKEY_CURRENT_USER`. Not set is returned as empty with [`EnvVarType::NotSet`]
+pub fn get_raw(hive: HKEY, subpath: &str, name: &str) -> cu::Result<(String, EnvVarType)> {
+    let raw = cu::check!(
+        get_raw_from_key_path(name, hive, subpath),
+        "failed to get '{name}' under '{subpath}'"
+    )?;
+    match raw {
+        Some(raw) => decode_raw(&raw),
+        None => Ok((String::new(), EnvVarType::NotSet)),
+    }
+}
+
+/// Set the value with the type to any registry location. See [`get_raw`]
+pub fn set_raw(
+    hive: HKEY,
+    subpath: &str,
+    name: &str,
+    value: &str,
+    value_type: EnvVarType,
+) -> cu::Result<()> {
+    check_value_len(name, value)?;
+    let raw = encode_raw(value, value_type)?;
+    cu::check!(
+        set_raw_to_key_path(name, hive, subpath, &raw),
+        "failed to set '{name}' under '{subpath}'"
+    )?;
+    after_write();
+    Ok(())
+}
+
 /// Set system environment variable.
+///
+/// The type of the existing value is preserved (i.e. `PATH` stays `REG_EXPAND_SZ`),
+/// and new values are written as `REG_SZ`
 pub fn set_system(key: &str, value: &str) -> cu::Result<()> {
     cu::check!(
-        set_from_key_path(key, HKEY_LOCAL_MACHINE, SYSTEM_PATH, value),
+        set_from_key_path(key, HKEY_LOCAL_MACHINE, SYSTEM_PATH, value, None),
         "failed to set system environment variable '{key}'"
-    )
+    )?;
+    after_write();
+    Ok(())
 }
 
 /// Set user environment variable.
+///
+/// The type of the existing value is preserved (i.e. `PATH` stays `REG_EXPAND_SZ`),
+/// and new values are written as `REG_SZ`
 pub fn set_user(key: &str, value: &str) -> cu::Result<()> {
     cu::check!(
-        set_from_key_path(key, HKEY_CURRENT_USER, USER_PATH, value),
+        set_from_key_path(key, HKEY_CURRENT_USER, USER_PATH, value, None),
         "failed to set user environment variable '{key}'"
+    )?;
+    after_write();
+    Ok(())
+}
+
+/// Set multiple system environment variables, opening the registry key only once.
+///
+/// Types of existing values are preserved like [`set_system`]. Stops at the first failure,
+/// and the values before it stay written
+pub fn set_system_many(pairs: &[(&str, &str)]) -> cu::Result<()> {
+    let (written, result) = set_many_in_key_path(HKEY_LOCAL_MACHINE, SYSTEM_PATH, pairs);
+    if written > 0 {
+        after_write();
+    }
+    cu::check!(result, "failed to set system environment variables")
+}
+
+/// Set multiple user environment variables, opening the registry key only once.
+///
+/// Types of existing values are preserved like [`set_user`]. Stops at the first failure,
+/// and the values before it stay written
+pub fn set_user_many(pairs: &[(&str, &str)]) -> cu::Result<()> {
+    let (written, result) = set_many_in_key_path(HKEY_CURRENT_USER, USER_PATH, pairs);
+    if written > 0 {
+        after_write();
+    }
+    cu::check!(result, "failed to set user environment variables")
+}
+
+/// Set system environment variable as `REG_EXPAND_SZ`, so `%VAR%` references are expanded
+pub fn set_system_expand(key: &str, value: &str) -> cu::Result<()> {
+    cu::check!(
+        set_from_key_path(
+            key,
+            HKEY_LOCAL_MACHINE,
+            SYSTEM_PATH,
+            value,
+            Some(REG_EXPAND_SZ)
+        ),
+        "failed to set system environment variable '{key}'"
+    )?;
+    after_write();
+    Ok(())
+}
+
+/// Set user environment variable as `REG_EXPAND_SZ`, so `%VAR%` references are expanded
+pub fn set_user_expand(key: &str, value: &str) -> cu::Result<()> {
+    cu::check!(
+        set_from_key_path(
+            key,
+            HKEY_CURRENT_USER,
+            USER_PATH,
+            value,
+            Some(REG_EXPAND_SZ)
+        ),
+        "failed to set user environment variable '{key}'"
+    )?;
+    after_write();
+    Ok(())
+}
+
+/// Delete system environment variable. Not set is treated as success
+pub fn delete_system(key: &str) -> cu::Result<()> {
+    cu::check!(
+        delete_from_key_path(key, HKEY_LOCAL_MACHINE, SYSTEM_PATH),
+        "failed to delete system environment variable '{key}'"
+    )?;
+    after_write();
+    Ok(())
+}
+
+/// Delete user environment variable. Not set is treated as success
+pub fn delete_user(key: &str) -> cu::Result<()> {
+    cu::check!(
+        delete_from_key_path(key, HKEY_CURRENT_USER, USER_PATH),
+        "failed to delete user environment variable '{key}'"
+    )?;
+    after_write();
+    Ok(())
+}
+
+/// Rename a system environment variable, preserving the registry value type.
+///
+/// Fails if the new name is already set, unless `force` is true
+pub fn rename_system(old: &str, new: &str, force: bool) -> cu::Result<()> {
+    cu::check!(
+        rename_in_key_path(old, new, HKEY_LOCAL_MACHINE, SYSTEM_PATH, force),
+        "failed to rename system environment variable '{old}' to '{new}'"
+    )?;
+    after_write();
+    Ok(())
+}
+
+/// Rename a user environment variable, preserving the registry value type.
+///
+/// Fails if the new name is already set, unless `force` is true
+pub fn rename_user(old: &str, new: &str, force: bool) -> cu::Result<()> {
+    cu::check!(
+        rename_in_key_path(old, new, HKEY_CURRENT_USER, USER_PATH, force),
+        "failed to rename user environment variable '{old}' to '{new}'"
+    )?;
+    after_write();
+    Ok(())
+}
+
+/// List all system environment variables
+pub fn list_system() -> cu::Result<BTreeMap<String, String>> {
+    cu::check!(
+        list_from_key_path(HKEY_LOCAL_MACHINE, SYSTEM_PATH),
+        "failed to list system environment variables"
+    )
+}
+
+/// List all user environment variables
+pub fn list_user() -> cu::Result<BTreeMap<String, String>> {
+    cu::check!(
+        list_from_key_path(HKEY_CURRENT_USER, USER_PATH),
+        "failed to list user environment variables"
+    )
+}
+
+/// List the names of all system environment variables, sorted.
+/// This is cheaper than listing since the values are not read
+pub fn list_names_system() -> cu::Result<Vec<String>> {
+    cu::check!(
+        list_names_from_key_path(HKEY_LOCAL_MACHINE, SYSTEM_PATH),
+        "failed to list system environment variable names"
+    )
+}
+
+/// List the names of all user environment variables, sorted.
+/// This is cheaper than listing since the values are not read
+pub fn list_names_user() -> cu::Result<Vec<String>> {
+    cu::check!(
+        list_names_from_key_path(HKEY_CURRENT_USER, USER_PATH),
+        "failed to list user environment variable names"
+    )
+}
+
+/// Expand `%NAME%` references in the value against the current process environment,
+/// like `ExpandEnvironmentStringsW`. References to variables that are not set
+/// are kept as-is.
+///
+/// This is useful for showing the expanded form of `REG_EXPAND_SZ` values like `PATH`
+pub fn expand(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    let mut rest = value;
+    while let Some(start) = rest.find('%') {
+        out.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        let Some(end) = after.find('%') else {
+            // unterminated reference
+            out.push_str(&rest[start..]);
+            return out;
+        };
+        let name = &after[..end];
+        match std::env::var(name) {
+            Ok(value) if !name.is_empty() => {
+                out.push_str(&value);
+                rest = &after[end + 1..];
+            }
+            _ => {
+                // keep the first % and try again from the second one,
+                // since it could start a reference like in `100%%PATH%`
+                out.push('%');
+                out.push_str(name);
+                rest = &after[end..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// A change to an environment variable
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EnvChange {
+    /// The variable was not set before
+    Added { name: String, value: String },
+    /// The variable is no longer set
+    Removed { name: String, value: String },
+    /// The value of the variable changed
+    Changed {
+        name: String,
+        old: String,
+        new: String,
+    },
+}
+
+/// Compare two sets of environment variables (for example, from [`list_user`]
+/// before and after running an installer), and return the changes from `a` to `b`,
+/// ordered by name
+pub fn diff(a: &BTreeMap<String, String>, b: &BTreeMap<String, String>) -> Vec<EnvChange> {
+    let mut changes = Vec::new();
+    for (name, old) in a {
+        match b.get(name) {
+            None => changes.push(EnvChange::Removed {
+                name: name.clone(),
+                value: old.clone(),
+            }),
+            Some(new) if new != old => changes.push(EnvChange::Changed {
+                name: name.clone(),
+                old: old.clone(),
+                new: new.clone(),
+            }),
+            Some(_) => {}
+        }
+    }
+    for (name, value) in b {
+        if !a.contains_key(name) {
+            changes.push(EnvChange::Added {
+                name: name.clone(),
+                value: value.clone(),
+            });
+        }
+    }
+    changes.sort_by(|x, y| x.name().cmp(y.name()));
+    changes
+}
+
+impl EnvChange {
+    /// Name of the changed variable
+    pub fn name(&self) -> &str {
+        match self {
+            Self::Added { name, .. } | Self::Removed { name, .. } | Self::Changed { name, .. } => {
+                name
+            }
+        }
+    }
+}
+
+/// Copy a user environment variable to system, preserving the registry value type.
+///
+/// If `merge` is true, the value is treated as `;`-delimited (like `PATH`), and entries
+/// not already in the system value are appended to it, instead of overwriting it.
+/// Returns the change made to the system variable, or `None` if nothing changed
+pub fn copy_user_to_system(key: &str, merge: bool) -> cu::Result<Option<EnvChange>> {
+    cu::check!(
+        copy_between_key_paths(
+            key,
+            (HKEY_CURRENT_USER, USER_PATH),
+            (HKEY_LOCAL_MACHINE, SYSTEM_PATH),
+            merge
+        ),
+        "failed to copy '{key}' from user to system"
+    )
+}
+
+/// Copy a system environment variable to user, preserving the registry value type.
+///
+/// If `merge` is true, the value is treated as `;`-delimited (like `PATH`), and entries
+/// not already in the user value are appended to it, instead of overwriting it.
+/// Returns the change made to the user variable, or `None` if nothing changed
+pub fn copy_system_to_user(key: &str, merge: bool) -> cu::Result<Option<EnvChange>> {
+    cu::check!(
+        copy_between_key_paths(
+            key,
+            (HKEY_LOCAL_MACHINE, SYSTEM_PATH),
+            (HKEY_CURRENT_USER, USER_PATH),
+            merge
+        ),
+        "failed to copy '{key}' from system to user"
+    )
+}
+
+/// Append the directory to the user `PATH`, if it's not already in it.
+/// Returns true if the value is changed
+pub fn append_user_path(dir: &str) -> cu::Result<bool> {
+    cu::check!(
+        add_path_entry("PATH", HKEY_CURRENT_USER, USER_PATH, dir, false),
+        "failed to append '{dir}' to user PATH"
+    )
+}
+
+/// Prepend the directory to the user `PATH`, if it's not already in it.
+/// Returns true if the value is changed
+pub fn prepend_user_path(dir: &str) -> cu::Result<bool> {
+    cu::check!(
+        add_path_entry("PATH", HKEY_CURRENT_USER, USER_PATH, dir, true),
+        "failed to prepend '{dir}' to user PATH"
+    )
+}
+
+/// Append the directory to the system `PATH`, if it's not already in it.
+/// Returns true if the value is changed
+pub fn append_system_path(dir: &str) -> cu::Result<bool> {
+    cu::check!(
+        add_path_entry("PATH", HKEY_LOCAL_MACHINE, SYSTEM_PATH, dir, false),
+        "failed to append '{dir}' to system PATH"
+    )
+}
+
+/// Prepend the directory to the system `PATH`, if it's not already in it.
+/// Returns true if the value is changed
+pub fn prepend_system_path(dir: &str) -> cu::Result<bool> {
+    cu::check!(
+        add_path_entry("PATH", HKEY_LOCAL_MACHINE, SYSTEM_PATH, dir, true),
+        "failed to prepend '{dir}' to system PATH"
+    )
+}
+
+/// Remove the directory from the user `PATH` (case-insensitive, trailing-slash-insensitive).
+/// Returns true if anything is removed
+pub fn remove_user_path(dir: &str) -> cu::Result<bool> {
+    cu::check!(
+        remove_path_entry("PATH", HKEY_CURRENT_USER, USER_PATH, dir),
+        "failed to remove '{dir}' from user PATH"
+    )
+}
+
+/// Remove the directory from the system `PATH` (case-insensitive, trailing-slash-insensitive).
+/// Returns true if anything is removed
+pub fn remove_system_path(dir: &str) -> cu::Result<bool> {
+    cu::check!(
+        remove_path_entry("PATH", HKEY_LOCAL_MACHINE, SYSTEM_PATH, dir),
+        "failed to remove '{dir}' from system PATH"
+    )
+}
+
+fn add_path_entry(
+    name: &str,
+    key: HKEY,
+    subpath: &str,
+    dir: &str,
+    prepend: bool,
+) -> cu::Result<bool> {
+    let raw = get_raw_from_key_path(name, key, subpath)?;
+    let (value, vtype) = match raw {
+        Some(raw) => (
+            cu::check!(String::from_reg_value(&raw), "value is not a string")?,
+            raw.vtype,
+        ),
+        // PATH-like variables are conventionally REG_EXPAND_SZ
+        None => (String::new(), REG_EXPAND_SZ),
+    };
+    if path_contains(&value, dir) {
+        return Ok(false);
+    }
+    let value = value.trim_matches(';');
+    let new_value = if value.is_empty() {
+        dir.to_string()
+    } else if prepend {
+        format!("{dir};{value}")
+    } else {
+        format!("{value};{dir}")
+    };
+    set_from_key_path(name, key, subpath, &new_value, Some(vtype))?;
+    after_write();
+    Ok(true)
+}
+
+fn remove_path_entry(name: &str, key: HKEY, subpath: &str, dir: &str) -> cu::Result<bool> {
+    let Some(raw) = get_raw_from_key_path(name, key, subpath)? else {
+        return Ok(false);
+    };
+    let value = cu::check!(String::from_reg_value(&raw), "value is not a string")?;
+    let normalized = normalize_path_entry(dir);
+    let entries = value.split(';').collect::<Vec<_>>();
+    let kept = entries
+        .iter()
+        .copied()
+        .filter(|x| normalize_path_entry(x) != normalized)
+        .collect::<Vec<_>>();
+    if kept.len() == entries.len() {
+        return Ok(false);
+    }
+    set_from_key_path(name, key, subpath, &kept.join(";"), Some(raw.vtype))?;
+    after_write();
+    Ok(true)
+}
+
+fn copy_between_key_paths(
+    name: &str,
+    from: (HKEY, &str),
+    to: (HKEY, &str),
+    merge: bool,
+) -> cu::Result<Option<EnvChange>> {
+    let Some(raw) = get_raw_from_key_path(name, from.0, from.1)? else {
+        cu::bail!("'{name}' is not set");
+    };
+    let (value, value_type) = decode_raw(&raw)?;
+    let (old, old_type) = match get_raw_from_key_path(name, to.0, to.1)? {
+        Some(raw) => {
+            let (old, old_type) = decode_raw(&raw)?;
+            (Some(old), old_type)
+        }
+        None => (None, EnvVarType::NotSet),
+    };
+    let new_value = match &old {
+        Some(old) if merge => {
+            let mut merged = old.trim_matches(';').to_string();
+            for entry in value.split(';').filter(|x| !x.is_empty()) {
+                if !path_contains(&merged, entry) {
+                    if !merged.is_empty() {
+                        merged.push(';');
+                    }
+                    merged.push_str(entry);
+                }
+            }
+            merged
+        }
+        _ => value,
+    };
+    if old.as_deref() == Some(new_value.as_str()) && old_type == value_type {
+        return Ok(None);
+    }
+    check_value_len(name, &new_value)?;
+    let raw = encode_raw(&new_value, value_type)?;
+    set_raw_to_key_path(name, to.0, to.1, &raw)?;
+    after_write();
+    let name = name.to_string();
+    let change = match old {
+        Some(old) => EnvChange::Changed {
+            name,
+            old,
+            new: new_value,
+        },
+        None => EnvChange::Added {
+            name,
+            value: new_value,
+        },
+    };
+    Ok(Some(change))
+}
+
+/// Check if the `;`-delimited value contains the directory,
+/// case-insensitive and trailing-slash-insensitive
+fn path_contains(value: &str, dir: &str) -> bool {
+    let dir = normalize_path_entry(dir);
+    value.split(';').any(|x| normalize_path_entry(x) == dir)
+}
+
+fn normalize_path_entry(entry: &str) -> String {
+    entry.trim().trim_end_matches(['\\', '/']).to_lowercase()
+}
+
+/// Get the raw value. Not set is returned as `None`
+fn get_raw_from_key_path(name: &str, key: HKEY, subpath: &str) -> cu::Result<Option<RegValue>> {
+    let reg_key = cu::check!(
+        RegKey::predef(key).open_subkey(subpath),
+        "open_subkey failed"
+    )?;
+    match reg_key.get_raw_value(name) {
+        Ok(value) => Ok(Some(value)),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+        Err(e) => {
+            cu::rethrow!(e, "failed to get reg key value");
+        }
+    }
+}
+
+/// Decode the raw value as string, with its type. `REG_MULTI_SZ` is joined with new lines
+fn decode_raw(raw: &RegValue) -> cu::Result<(String, EnvVarType)> {
+    let value = cu::check!(String::from_reg_value(raw), "value is not a string")?;
+    Ok((value, raw.vtype.clone().into()))
+}
+
+/// Encode the string value with the type. `REG_MULTI_SZ` is split by new lines
+fn encode_raw(value: &str, value_type: EnvVarType) -> cu::Result<RegValue> {
+    let mut raw = match value_type {
+        EnvVarType::MultiSz => {
+            let values = value.split('\n').map(str::to_string).collect::<Vec<_>>();
+            return Ok(values.to_reg_value());
+        }
+        EnvVarType::Sz | EnvVarType::ExpandSz => value.to_reg_value(),
+        EnvVarType::NotSet | EnvVarType::Other => {
+            cu::bail!("cannot encode value as {}", value_type.as_str());
+        }
+    };
+    if value_type == EnvVarType::ExpandSz {
+        raw.vtype = REG_EXPAND_SZ;
+    }
+    Ok(raw)
+}
+
+fn set_raw_to_key_path(name: &str, key: HKEY, subpath: &str, raw: &RegValue) -> cu::Result<()> {
+    let reg_key = cu::check!(
+        RegKey::predef(key).open_subkey_with_flags(subpath, KEY_WRITE),
+        "failed to open_subkey with write flag"
+    )?;
+    cu::check!(
+        reg_key.set_raw_value(name, raw),
+        "failed to set reg key value"
     )
 }
 
@@ -56,13 +760,235 @@ fn get_from_key_path(name: &str, key: HKEY, subpath: &str) -> cu::Result<String>
     }
 }
 
-fn set_from_key_path(name: &str, key: HKEY, subpath: &str, value: &str) -> cu::Result<()> {
+/// Set the value. If `vtype` is `None`, the type of the existing value is preserved
+fn set_from_key_path(
+    name: &str,
+    key: HKEY,
+    subpath: &str,
+    value: &str,
+    vtype: Option<RegType>,
+) -> cu::Result<()> {
     let reg_key = cu::check!(
-        RegKey::predef(key).open_subkey_with_flags(subpath, KEY_WRITE),
+        RegKey::predef(key).open_subkey_with_flags(subpath, KEY_READ | KEY_WRITE),
         "failed to open_subkey with write flag"
     )?;
+    set_in_key(&reg_key, name, value, vtype)
+}
+
+/// Set multiple values with the key opened once. Types of existing values are preserved.
+/// Returns the number of values written before the first failure, if any
+fn set_many_in_key_path(
+    key: HKEY,
+    subpath: &str,
+    pairs: &[(&str, &str)],
+) -> (usize, cu::Result<()>) {
+    let reg_key = match cu::check!(
+        RegKey::predef(key).open_subkey_with_flags(subpath, KEY_READ | KEY_WRITE),
+        "failed to open_subkey with write flag"
+    ) {
+        Ok(x) => x,
+        Err(e) => return (0, Err(e)),
+    };
+    for (i, (name, value)) in pairs.iter().enumerate() {
+        if let Err(e) = cu::check!(
+            set_in_key(&reg_key, name, value, None),
+            "failed to set '{name}'"
+        ) {
+            return (i, Err(e));
+        }
+    }
+    (pairs.len(), Ok(()))
+}
+
+fn set_in_key(reg_key: &RegKey, name: &str, value: &str, vtype: Option<RegType>) -> cu::Result<()> {
+    check_value_len(name, value)?;
+    let vtype = match vtype {
+        Some(vtype) => vtype,
+        None => match reg_key.get_raw_value(name) {
+            Ok(existing) if existing.vtype == REG_EXPAND_SZ => REG_EXPAND_SZ,
+            _ => REG_SZ,
+        },
+    };
+    let mut raw = value.to_reg_value();
+    raw.vtype = vtype;
     cu::check!(
-        reg_key.set_value(name, &value),
+        reg_key.set_raw_value(name, &raw),
         "failed to set reg key value"
     )
 }
+
+fn rename_in_key_path(
+    old: &str,
+    new: &str,
+    key: HKEY,
+    subpath: &str,
+    force: bool,
+) -> cu::Result<()> {
+    let reg_key = cu::check!(
+        RegKey::predef(key).open_subkey_with_flags(subpath, KEY_READ | KEY_WRITE),
+        "failed to open_subkey with write flag"
+    )?;
+    let raw = match reg_key.get_raw_value(old) {
+        Ok(raw) => raw,
+        Err(e) if e.kind() == ErrorKind::NotFound => cu::bail!("'{old}' is not set"),
+        Err(e) => cu::rethrow!(e, "failed to get reg key value"),
+    };
+    // value names are case-insensitive, so renaming to change the case is allowed
+    if !old.eq_ignore_ascii_case(new) && !force {
+        match reg_key.get_raw_value(new) {
+            Ok(_) => cu::bail!("'{new}' is already set"),
+            Err(e) if e.kind() == ErrorKind::NotFound => {}
+            Err(e) => cu::rethrow!(e, "failed to get reg key value"),
+        }
+    }
+    if old.eq_ignore_ascii_case(new) {
+        // delete first, otherwise setting would only update the value
+        cu::check!(reg_key.delete_value(old), "failed to delete reg key value")?;
+        if let Err(e) = reg_key.set_raw_value(new, &raw) {
+            // put the original value back, so the variable is not lost
+            if let Err(restore_error) = reg_key.set_raw_value(old, &raw) {
+                cu::error!("failed to restore '{old}' after failed rename: {restore_error}");
+            }
+            cu::rethrow!(e, "failed to set reg key value");
+        }
+        return Ok(());
+    }
+    cu::check!(
+        reg_key.set_raw_value(new, &raw),
+        "failed to set reg key value"
+    )?;
+    cu::check!(reg_key.delete_value(old), "failed to delete reg key value")
+}
+
+fn delete_from_key_path(name: &str, key: HKEY, subpath: &str) -> cu::Result<()> {
+    let reg_key = cu::check!(
+        RegKey::predef(key).open_subkey_with_flags(subpath, KEY_WRITE),
+        "failed to open_subkey with write flag"
+    )?;
+    match reg_key.delete_value(name) {
+        Ok(()) => Ok(()),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(()),
+        Err(e) => {
+            cu::rethrow!(e, "failed to delete reg key value");
+        }
+    }
+}
+
+fn list_from_key_path(key: HKEY, subpath: &str) -> cu::Result<BTreeMap<String, String>> {
+    let reg_key = cu::check!(
+        RegKey::predef(key).open_subkey(subpath),
+        "open_subkey failed"
+    )?;
+    let mut values = BTreeMap::new();
+    for result in reg_key.enum_values() {
+        let (name, raw) = cu::check!(result, "failed to enumerate reg key values")?;
+        // REG_SZ, REG_EXPAND_SZ and REG_MULTI_SZ are all decoded as string
+        match String::from_reg_value(&raw) {
+            Ok(value) => {
+                values.insert(name, value);
+            }
+            Err(e) => {
+                cu::debug!("skipping non-string value '{name}': {e}");
+            }
+        }
+    }
+    Ok(values)
+}
+
+/// Enumerate value names with `RegEnumValueW`, without reading the data
+fn list_names_from_key_path(key: HKEY, subpath: &str) -> cu::Result<Vec<String>> {
+    use windows_sys::Win32::Foundation::{ERROR_NO_MORE_ITEMS, ERROR_SUCCESS};
+    use windows_sys::Win32::System::Registry::RegEnumValueW;
+    let reg_key = cu::check!(
+        RegKey::predef(key).open_subkey(subpath),
+        "open_subkey failed"
+    )?;
+    let info = cu::check!(reg_key.query_info(), "failed to query reg key info")?;
+    // max_value_name_len does not include the null terminator
+    let mut buf = vec![0u16; info.max_value_name_len as usize + 1];
+    let mut names = Vec::with_capacity(info.values as usize);
+    for index in 0.. {
+        let mut len = buf.len() as u32;
+        // SAFETY: buf is valid for len u16s, and the type/data outputs are optional
+        let ret = unsafe {
+            RegEnumValueW(
+                reg_key.raw_handle(),
+                index,
+                buf.as_mut_ptr(),
+                &mut len,
+                std::ptr::null(),
+                std::ptr::null_mut(),
+                std::ptr::null_mut(),
+                std::ptr::null_mut(),
+            )
+        };
+        match ret {
+            ERROR_SUCCESS => {}
+            ERROR_NO_MORE_ITEMS => break,
+            e => cu::rethrow!(
+                std::io::Error::from_raw_os_error(e as i32),
+                "failed to enumerate reg key values"
+            ),
+        }
+        let name = cu::check!(
+            String::from_utf16(&buf[..len as usize]),
+            "value name is not valid UTF-16"
+        )?;
+        names.push(name);
+    }
+    names.sort();
+    Ok(names)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    #[test]
+    fn test_diff() {
+        let a = BTreeMap::from([
+            ("FOO".to_string(), "1".to_string()),
+            ("PATH".to_string(), "C:\\a".to_string()),
+            ("OLD".to_string(), "x".to_string()),
+        ]);
+        let b = BTreeMap::from([
+            ("FOO".to_string(), "1".to_string()),
+            ("PATH".to_string(), "C:\\a;C:\\b".to_string()),
+            ("NEW".to_string(), "y".to_string()),
+        ]);
+        assert_eq!(
+            diff(&a, &b),
+            vec![
+                EnvChange::Added {
+                    name: "NEW".to_string(),
+                    value: "y".to_string()
+                },
+                EnvChange::Removed {
+                    name: "OLD".to_string(),
+                    value: "x".to_string()
+                },
+                EnvChange::Changed {
+                    name: "PATH".to_string(),
+                    old: "C:\\a".to_string(),
+                    new: "C:\\a;C:\\b".to_string()
+                },
+            ]
+        );
+        assert!(diff(&a, &a).is_empty());
+    }
+
+    #[test]
+    fn test_max_value_len() {
+        let long = "a".repeat(PRACTICAL_MAX_VALUE_LEN + 1);
+        // no limit by default
+        assert!(check_value_len("PATH", &long).is_ok());
+        set_max_value_len(PRACTICAL_MAX_VALUE_LEN);
+        assert!(check_value_len("PATH", &long).is_err());
+        assert!(check_value_len("PATH", &long[1..]).is_ok());
+        set_max_value_len(1);
+        assert!(check_value_len("PATH", "a").is_ok());
+        assert!(check_value_len("PATH", "ab").is_err());
+        // 0 means no limit
+        set_max_value_len(0);
+        assert!(check_value_len("PATH", &long).is_ok());
+    }
+}
@@ -0,0 +1,221 @@
+// SPDX-License-Identifier: MIT
+// Copyright (c) 2026 Pistonite
+
+use std::path::{Path, PathBuf};
+
+use cu::pre::*;
+use winreg::HKEY;
+use winreg::enums::{HKEY_CURRENT_USER, HKEY_LOCAL_MACHINE};
+
+use super::{
+    EnvVarType, SYSTEM_PATH, USER_PATH, after_write, decode_raw, delete_from_key_path, encode_raw,
+    get_raw_from_key_path, set_raw_to_key_path,
+};
+
+static HEADER: &str = "win-envedit backup v1";
+
+/// Set system environment variable, and return a backup of the old value
+/// that can be restored. See [`Backup`]
+pub fn set_system_with_backup(key: &str, value: &str) -> cu::Result<Backup> {
+    let backup = Backup::create(true, key)?;
+    if let Err(e) = super::set_system(key, value) {
+        // nothing is changed, so the backup is not needed
+        backup.discard();
+        return Err(e);
+    }
+    Ok(backup)
+}
+
+/// Set user environment variable, and return a backup of the old value
+/// that can be restored. See [`Backup`]
+pub fn set_user_with_backup(key: &str, value: &str) -> cu::Result<Backup> {
+    let backup = Backup::create(false, key)?;
+    if let Err(e) = super::set_user(key, value) {
+        // nothing is changed, so the backup is not needed
+        backup.discard();
+        return Err(e);
+    }
+    Ok(backup)
+}
+
+/// Backup of an environment variable, with its registry type.
+///
+/// The backup is also persisted to a file (in `%LOCALAPPDATA%\win-envedit\backup`),
+/// so it can be loaded with [`Backup::load`] and restored if the process crashes
+/// before restoring. The file is removed on [`restore`](Self::restore) or
+/// [`discard`](Self::discard)
+#[derive(Debug)]
+pub struct Backup {
+    system: bool,
+    name: String,
+    value_type: EnvVarType,
+    value: String,
+    file: PathBuf,
+}
+
+impl Backup {
+    /// Back up the current value and persist it to a file
+    fn create(system: bool, name: &str) -> cu::Result<Self> {
+        let (key, subpath) = hive(system);
+        let raw = cu::check!(
+            get_raw_from_key_path(name, key, subpath),
+            "failed to read '{name}' for backup"
+        )?;
+        let (value, value_type) = match raw {
+            Some(raw) => decode_raw(&raw)?,
+            None => (String::new(), EnvVarType::NotSet),
+        };
+        if value_type == EnvVarType::Other {
+            cu::bail!("cannot back up '{name}': not a string value");
+        }
+        let dir = backup_dir();
+        cu::check!(
+            std::fs::create_dir_all(&dir),
+            "failed to create backup directory '{}'",
+            dir.display()
+        )?;
+        let timestamp = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|x| x.as_millis())
+            .unwrap_or_default();
+        let file = dir.join(format!("{name}-{timestamp}.txt"));
+        let backup = Self {
+            system,
+            name: name.to_string(),
+            value_type,
+            value,
+            file,
+        };
+        cu::check!(
+            std::fs::write(&backup.file, backup.serialize()),
+            "failed to write backup file '{}'",
+            backup.file.display()
+        )?;
+        cu::debug!("backed up '{name}' to '{}'", backup.file.display());
+        Ok(backup)
+    }
+
+    /// Load a backup persisted to the file
+    pub fn load(path: impl AsRef<Path>) -> cu::Result<Self> {
+        let path = path.as_ref();
+        let content = cu::check!(
+            std::fs::read_to_string(path),
+            "failed to read backup file '{}'",
+            path.display()
+        )?;
+        let backup = cu::check!(
+            Self::deserialize(&content, path),
+            "failed to parse backup file '{}'",
+            path.display()
+        )?;
+        Ok(backup)
+    }
+
+    /// Path to the file the backup is persisted to
+    pub fn file(&self) -> &Path {
+        &self.file
+    }
+
+    /// Name of the backed up variable
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The old value, and its type. The value is empty if the variable was not set
+    pub fn value(&self) -> (&str, EnvVarType) {
+        (&self.value, self.value_type)
+    }
+
+    /// Write the old value (with its type) back, and remove the backup file.
+    /// If the variable was not set, it's deleted
+    pub fn restore(&self) -> cu::Result<()> {
+        let (key, subpath) = hive(self.system);
+        let name = &self.name;
+        if self.value_type == EnvVarType::NotSet {
+            cu::check!(
+                delete_from_key_path(name, key, subpath),
+                "failed to restore '{name}'"
+            )?;
+        } else {
+            let raw = encode_raw(&self.value, self.value_type)?;
+            cu::check!(
+                set_raw_to_key_path(name, key, subpath, &raw),
+                "failed to restore '{name}'"
+            )?;
+        }
+        after_write();
+        self.remove_file();
+        Ok(())
+    }
+
+    /// Keep the new value and remove the backup file
+    pub fn discard(self) {
+        self.remove_file();
+    }
+
+    fn remove_file(&self) {
+        if let Err(e) = std::fs::remove_file(&self.file) {
+            cu::warn!(
+                "failed to remove backup file '{}': {e}",
+                self.file.display()
+            );
+        }
+    }
+
+    /// The value is last since it can contain new lines
+    fn serialize(&self) -> String {
+        format!(
+            "{HEADER}\nscope: {}\nname: {}\ntype: {}\n{}",
+            if self.system { "system" } else { "user" },
+            self.name,
+            self.value_type.as_str(),
+            self.value
+        )
+    }
+
+    fn deserialize(content: &str, path: &Path) -> cu::Result<Self> {
+        let mut parts = content.splitn(5, '\n');
+        if parts.next() != Some(HEADER) {
+            cu::bail!("not a backup file");
+        }
+        let mut field = |name: &str| {
+            let line = parts.next().unwrap_or_default();
+            match line.strip_prefix(name).and_then(|x| x.strip_prefix(": ")) {
+                Some(x) => Ok(x),
+                None => cu::bail!("missing field '{name}'"),
+            }
+        };
+        let system = match field("scope")? {
+            "system" => true,
+            "user" => false,
+            x => cu::bail!("invalid scope '{x}'"),
+        };
+        let name = field("name")?.to_string();
+        let value_type = field("type")?;
+        let value_type = cu::check!(EnvVarType::parse(value_type), "invalid type '{value_type}'")?;
+        let value = parts.next().unwrap_or_default().to_string();
+        Ok(Self {
+            system,
+            name,
+            value_type,
+            value,
+            file: path.to_path_buf(),
+        })
+    }
+}
+
+fn hive(system: bool) -> (HKEY, &'static str) {
+    if system {
+        (HKEY_LOCAL_MACHINE, SYSTEM_PATH)
+    } else {
+        (HKEY_CURRENT_USER, USER_PATH)
+    }
+}
+
+fn backup_dir() -> PathBuf {
+    let base = match std::env::var_os("LOCALAPPDATA") {
+        Some(x) if !x.is_empty() => PathBuf::from(x),
+        _ => std::env::temp_dir(),
+    };
+    base.join("win-envedit").join("backup")
+}
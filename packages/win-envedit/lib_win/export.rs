@@ -0,0 +1,261 @@
+// SPDX-License-Identifier: MIT
+// Copyright (c) 2026 Pistonite
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use cu::pre::*;
+use winreg::enums::{HKEY_CURRENT_USER, HKEY_LOCAL_MACHINE, KEY_READ, KEY_WRITE};
+use winreg::{HKEY, RegKey};
+
+use super::{EnvVarType, SYSTEM_PATH, USER_PATH, after_write, decode_raw, encode_raw};
+
+static HEADER: &str = "win-envedit export v1";
+
+/// Export all system environment variables, with their registry types, to a file.
+/// Values that are not strings are skipped. See [`import_system`]
+pub fn export_system(path: impl AsRef<Path>) -> cu::Result<()> {
+    let path = path.as_ref();
+    cu::check!(
+        export_key_path(HKEY_LOCAL_MACHINE, SYSTEM_PATH, path),
+        "failed to export system environment variables to '{}'",
+        path.display()
+    )
+}
+
+/// Export all user environment variables, with their registry types, to a file.
+/// Values that are not strings are skipped. See [`import_user`]
+pub fn export_user(path: impl AsRef<Path>) -> cu::Result<()> {
+    let path = path.as_ref();
+    cu::check!(
+        export_key_path(HKEY_CURRENT_USER, USER_PATH, path),
+        "failed to export user environment variables to '{}'",
+        path.display()
+    )
+}
+
+/// Restore system environment variables from a file created by [`export_system`].
+///
+/// Values are written with their exported types, and string variables that are
+/// not in the file are deleted, so the environment is the same as when exported
+pub fn import_system(path: impl AsRef<Path>) -> cu::Result<()> {
+    let path = path.as_ref();
+    cu::check!(
+        import_key_path(HKEY_LOCAL_MACHINE, SYSTEM_PATH, path),
+        "failed to import system environment variables from '{}'",
+        path.display()
+    )?;
+    after_write();
+    Ok(())
+}
+
+/// Restore user environment variables from a file created by [`export_user`].
+///
+/// Values are written with their exported types, and string variables that are
+/// not in the file are deleted, so the environment is the same as when exported
+pub fn import_user(path: impl AsRef<Path>) -> cu::Result<()> {
+    let path = path.as_ref();
+    cu::check!(
+        import_key_path(HKEY_CURRENT_USER, USER_PATH, path),
+        "failed to import user environment variables from '{}'",
+        path.display()
+    )?;
+    after_write();
+    Ok(())
+}
+
+/// Read the values in a file created by [`export_system`] or [`export_user`],
+/// for example, to [`diff`](super::diff) with the current environment
+pub fn read_export(path: impl AsRef<Path>) -> cu::Result<BTreeMap<String, String>> {
+    let path = path.as_ref();
+    let content = cu::check!(
+        std::fs::read_to_string(path),
+        "failed to read export file '{}'",
+        path.display()
+    )?;
+    let entries = cu::check!(
+        deserialize(&content),
+        "failed to parse export file '{}'",
+        path.display()
+    )?;
+    Ok(entries.into_iter().map(|x| (x.name, x.value)).collect())
+}
+
+/// An exported value
+struct Entry {
+    name: String,
+    value_type: EnvVarType,
+    value: String,
+}
+
+fn export_key_path(key: HKEY, subpath: &str, path: &Path) -> cu::Result<()> {
+    let entries = read_entries(key, subpath)?;
+    cu::check!(
+        std::fs::write(path, serialize(&entries)),
+        "failed to write export file"
+    )?;
+    cu::debug!("exported {} values", entries.len());
+    Ok(())
+}
+
+fn import_key_path(key: HKEY, subpath: &str, path: &Path) -> cu::Result<()> {
+    let content = cu::check!(std::fs::read_to_string(path), "failed to read export file")?;
+    let entries = cu::check!(deserialize(&content), "failed to parse export file")?;
+    // encode everything before writing, so a bad file doesn't leave a partial import
+    let mut values = Vec::with_capacity(entries.len());
+    for entry in &entries {
+        let raw = cu::check!(
+            encode_raw(&entry.value, entry.value_type),
+            "invalid value for '{}'",
+            entry.name
+        )?;
+        values.push((entry.name.as_str(), raw));
+    }
+    let current = read_entries(key, subpath)?;
+    let reg_key = cu::check!(
+        RegKey::predef(key).open_subkey_with_flags(subpath, KEY_READ | KEY_WRITE),
+        "failed to open_subkey with write flag"
+    )?;
+    for (name, raw) in &values {
+        cu::check!(
+            reg_key.set_raw_value(name, raw),
+            "failed to set reg key value '{name}'"
+        )?;
+    }
+    for entry in current {
+        let name = entry.name;
+        // value names are case-insensitive
+        if !values.iter().any(|(x, _)| x.eq_ignore_ascii_case(&name)) {
+            cu::check!(
+                reg_key.delete_value(&name),
+                "failed to delete reg key value '{name}'"
+            )?;
+        }
+    }
+    cu::debug!("imported {} values", values.len());
+    Ok(())
+}
+
+fn read_entries(key: HKEY, subpath: &str) -> cu::Result<Vec<Entry>> {
+    let reg_key = cu::check!(
+        RegKey::predef(key).open_subkey(subpath),
+        "open_subkey failed"
+    )?;
+    let mut entries = Vec::new();
+    for result in reg_key.enum_values() {
+        let (name, raw) = cu::check!(result, "failed to enumerate reg key values")?;
+        match decode_raw(&raw) {
+            Ok((value, value_type)) => entries.push(Entry {
+                name,
+                value_type,
+                value,
+            }),
+            Err(e) => {
+                cu::debug!("skipping non-string value '{name}': {e}");
+            }
+        }
+    }
+    Ok(entries)
+}
+
+/// Each entry is a `<type> <length> <name>` line, followed by the value,
+/// which can contain new lines (for `REG_MULTI_SZ`), and a new line
+fn serialize(entries: &[Entry]) -> String {
+    let mut out = format!("{HEADER}\n");
+    for entry in entries {
+        out.push_str(&format!(
+            "{} {} {}\n{}\n",
+            entry.value_type.as_str(),
+            entry.value.len(),
+            entry.name,
+            entry.value
+        ));
+    }
+    out
+}
+
+fn deserialize(content: &str) -> cu::Result<Vec<Entry>> {
+    let Some(mut rest) = content
+        .strip_prefix(HEADER)
+        .and_then(|x| x.strip_prefix('\n'))
+    else {
+        cu::bail!("not an export file");
+    };
+    let mut entries = Vec::new();
+    while !rest.is_empty() {
+        let Some((line, after)) = rest.split_once('\n') else {
+            cu::bail!("unexpected end of file");
+        };
+        let mut parts = line.splitn(3, ' ');
+        let (Some(value_type), Some(len), Some(name)) = (parts.next(), parts.next(), parts.next())
+        else {
+            cu::bail!("invalid entry '{line}'");
+        };
+        let value_type = cu::check!(EnvVarType::parse(value_type), "invalid type '{value_type}'")?;
+        let len = cu::check!(len.parse::<usize>(), "invalid length '{len}'")?;
+        let (Some(value), Some(after)) = (after.get(..len), after.get(len..)) else {
+            cu::bail!("value of '{name}' is truncated");
+        };
+        let Some(after) = after.strip_prefix('\n') else {
+            cu::bail!("value of '{name}' has the wrong length");
+        };
+        entries.push(Entry {
+            name: name.to_string(),
+            value_type,
+            value: value.to_string(),
+        });
+        rest = after;
+    }
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn entry(name: &str, value_type: EnvVarType, value: &str) -> Entry {
+        Entry {
+            name: name.to_string(),
+            value_type,
+            value: value.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_serialize_round_trip() {
+        let entries = vec![
+            entry(
+                "PATH",
+                EnvVarType::ExpandSz,
+                "%USERPROFILE%\\bin;C:\\Windows",
+            ),
+            entry("FOO", EnvVarType::Sz, "hello world"),
+            entry("LIST", EnvVarType::MultiSz, "a\nb\n\nc\n"),
+            entry("EMPTY", EnvVarType::Sz, ""),
+        ];
+        let parsed = deserialize(&serialize(&entries)).unwrap();
+        assert_eq!(parsed.len(), entries.len());
+        for (a, b) in parsed.iter().zip(&entries) {
+            assert_eq!(a.name, b.name);
+            assert_eq!(a.value_type, b.value_type);
+            assert_eq!(a.value, b.value);
+        }
+    }
+
+    #[test]
+    fn test_deserialize_invalid() {
+        assert!(deserialize("").is_err());
+        assert!(deserialize("FOO=bar\n").is_err());
+        // truncated
+        let content = serialize(&[entry("FOO", EnvVarType::Sz, "hello")]);
+        assert!(deserialize(&content[..content.len() - 3]).is_err());
+        assert!(deserialize(&format!("{HEADER}\nsz 5 FOO")).is_err());
+        // invalid length
+        assert!(deserialize(&format!("{HEADER}\nsz abc FOO\nhello\n")).is_err());
+        assert!(deserialize(&format!("{HEADER}\nsz -1 FOO\nhello\n")).is_err());
+        assert!(deserialize(&format!("{HEADER}\nsz 3 FOO\nhello\n")).is_err());
+        // invalid type
+        assert!(deserialize(&format!("{HEADER}\ndword 5 FOO\nhello\n")).is_err());
+        assert!(deserialize(&format!("{HEADER}\n5 FOO\nhello\n")).is_err());
+    }
+}
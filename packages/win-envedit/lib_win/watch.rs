@@ -0,0 +1,152 @@
+// SPDX-License-Identifier: MIT
+// Copyright (c) 2026 Pistonite
+
+use std::sync::Arc;
+
+use cu::pre::*;
+use windows_sys::Win32::Foundation::{CloseHandle, ERROR_SUCCESS, HANDLE, WAIT_OBJECT_0};
+use windows_sys::Win32::System::Registry::{
+    REG_NOTIFY_CHANGE_LAST_SET, REG_NOTIFY_CHANGE_NAME, REG_NOTIFY_THREAD_AGNOSTIC,
+    RegNotifyChangeKeyValue,
+};
+use windows_sys::Win32::System::Threading::{
+    CreateEventW, INFINITE, SetEvent, WaitForMultipleObjects,
+};
+use winreg::enums::{HKEY_CURRENT_USER, HKEY_LOCAL_MACHINE, KEY_NOTIFY};
+use winreg::{HKEY, RegKey};
+
+use super::{SYSTEM_PATH, USER_PATH};
+
+/// Watch for changes to the environment variables in the hive, which must be
+/// [`HKEY_LOCAL_MACHINE`] (system) or [`HKEY_CURRENT_USER`] (user).
+///
+/// The returned [`Watch`] is an iterator that blocks until a variable is set, deleted
+/// or renamed, and yields once for each change (multiple changes in a short period
+/// may be coalesced). It ends when cancelled with a [`WatchCanceller`], or on error.
+///
+/// # Threading
+/// The notification is registered with `RegNotifyChangeKeyValue`. The registration
+/// is thread-agnostic (requires Windows 8+), so the `Watch` can be moved to and iterated
+/// on another thread. Iterating blocks the thread, so it's recommended to watch
+/// on a dedicated thread, and cancel from another thread with [`Watch::canceller`]
+pub fn watch(hive: HKEY) -> cu::Result<Watch> {
+    let subpath = if hive == HKEY_LOCAL_MACHINE {
+        SYSTEM_PATH
+    } else if hive == HKEY_CURRENT_USER {
+        USER_PATH
+    } else {
+        cu::bail!("can only watch HKEY_LOCAL_MACHINE or HKEY_CURRENT_USER");
+    };
+    let key = cu::check!(
+        RegKey::predef(hive).open_subkey_with_flags(subpath, KEY_NOTIFY),
+        "failed to open_subkey with notify flag"
+    )?;
+    // auto-reset, so each wait consumes one notification
+    let event = Event::new(false)?;
+    // manual-reset, so it stays signaled once cancelled
+    let cancel = Arc::new(Event::new(true)?);
+    let watch = Watch { key, event, cancel };
+    // register before returning, so changes made before the first `next` are not missed
+    watch.register()?;
+    Ok(watch)
+}
+
+/// Iterator of environment change notifications. See [`watch`]
+pub struct Watch {
+    key: RegKey,
+    event: Event,
+    cancel: Arc<Event>,
+}
+
+impl Watch {
+    /// Get a handle that can cancel the watch from another thread
+    pub fn canceller(&self) -> WatchCanceller {
+        WatchCanceller(Arc::clone(&self.cancel))
+    }
+
+    fn register(&self) -> cu::Result<()> {
+        // SAFETY: the key and the event are valid handles owned by self
+        let ret = unsafe {
+            RegNotifyChangeKeyValue(
+                self.key.raw_handle(),
+                0,
+                REG_NOTIFY_CHANGE_NAME | REG_NOTIFY_CHANGE_LAST_SET | REG_NOTIFY_THREAD_AGNOSTIC,
+                self.event.0,
+                1,
+            )
+        };
+        if ret != ERROR_SUCCESS {
+            cu::rethrow!(
+                std::io::Error::from_raw_os_error(ret as i32),
+                "failed to register registry change notification"
+            );
+        }
+        Ok(())
+    }
+}
+
+impl Iterator for Watch {
+    type Item = ();
+
+    fn next(&mut self) -> Option<()> {
+        let handles = [self.event.0, self.cancel.0];
+        // SAFETY: both handles are valid events owned by self
+        let ret = unsafe { WaitForMultipleObjects(2, handles.as_ptr(), 0, INFINITE) };
+        if ret != WAIT_OBJECT_0 {
+            if ret != WAIT_OBJECT_0 + 1 {
+                let e = std::io::Error::last_os_error();
+                cu::error!("failed to wait for registry change: {e}");
+            }
+            return None;
+        }
+        // the notification is one-shot, register again for the next change
+        if let Err(e) = self.register() {
+            cu::error!("{e:?}");
+            return None;
+        }
+        Some(())
+    }
+}
+
+/// Handle to cancel a [`Watch`], which can be sent to other threads
+#[derive(Clone)]
+pub struct WatchCanceller(Arc<Event>);
+
+impl WatchCanceller {
+    /// Cancel the watch. A blocked `next` returns `None`, and so do further calls
+    pub fn cancel(&self) {
+        // SAFETY: the event is valid while the Arc is alive
+        if unsafe { SetEvent(self.0.0) } == 0 {
+            let e = std::io::Error::last_os_error();
+            cu::error!("failed to cancel watch: {e}");
+        }
+    }
+}
+
+/// Owned Win32 event handle
+struct Event(HANDLE);
+
+// SAFETY: event handles can be used from any thread
+unsafe impl Send for Event {}
+unsafe impl Sync for Event {}
+
+impl Event {
+    fn new(manual_reset: bool) -> cu::Result<Self> {
+        // SAFETY: no security attributes or name, initially not signaled
+        let handle =
+            unsafe { CreateEventW(std::ptr::null(), manual_reset.into(), 0, std::ptr::null()) };
+        if handle.is_null() {
+            cu::rethrow!(std::io::Error::last_os_error(), "failed to create event");
+        }
+        Ok(Self(handle))
+    }
+}
+
+impl Drop for Event {
+    fn drop(&mut self) {
+        // SAFETY: the handle is owned and not used after drop
+        unsafe {
+            CloseHandle(self.0);
+        }
+    }
+}
@@ -0,0 +1,39 @@
+// SPDX-License-Identifier: MIT
+// Copyright (c) 2026 Pistonite
+
+use cu::pre::*;
+
+#[cfg(windows)]
+mod win;
+#[cfg(windows)]
+use win::SystemClipboard;
+#[cfg(not(windows))]
+mod unix;
+#[cfg(not(windows))]
+use unix::SystemClipboard;
+
+/// The OS-specific part of the server
+pub trait Clipboard {
+    /// Set the clipboard to the text
+    fn set_text(&mut self, text: &str) -> cu::Result<()>;
+    /// Get the clipboard as text
+    fn get_text(&mut self) -> cu::Result<String>;
+    /// Set the clipboard to the HTML fragment
+    fn set_html(&mut self, html: &str) -> cu::Result<()>;
+    /// Set the clipboard to the image. `kind` is the MIME type, like `image/png`
+    fn set_image(&mut self, kind: &str, data: &[u8]) -> cu::Result<()>;
+}
+
+/// Format to set text in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum TextFormat {
+    /// `CF_UNICODETEXT` on Windows, or the default text format on other platforms
+    Unicode,
+    /// `CF_TEXT` in the system code page, for legacy apps. Only supported on Windows
+    Ansi,
+}
+
+/// Open the system clipboard
+pub fn open(format: TextFormat) -> cu::Result<Box<dyn Clipboard>> {
+    Ok(Box::new(SystemClipboard::new(format)?))
+}
@@ -0,0 +1,42 @@
+// SPDX-License-Identifier: MIT
+// Copyright (c) 2026 Pistonite
+
+use cu::pre::*;
+
+use super::{Clipboard, TextFormat};
+
+/// X11/Wayland clipboard on Linux, or the pasteboard on macOS
+pub struct SystemClipboard {
+    // on X11, the content is served by this process, so the clipboard
+    // needs to be kept alive for as long as the server is running
+    inner: arboard::Clipboard,
+}
+
+impl SystemClipboard {
+    pub fn new(format: TextFormat) -> cu::Result<Self> {
+        if format == TextFormat::Ansi {
+            cu::bail!("ANSI text format is only supported on Windows");
+        }
+        let inner = cu::check!(arboard::Clipboard::new(), "failed to open clipboard")?;
+        Ok(Self { inner })
+    }
+}
+
+impl Clipboard for SystemClipboard {
+    fn set_text(&mut self, text: &str) -> cu::Result<()> {
+        cu::check!(self.inner.set_text(text), "failed to set clipboard")
+    }
+
+    fn get_text(&mut self) -> cu::Result<String> {
+        cu::check!(self.inner.get_text(), "failed to get clipboard as text")
+    }
+
+    fn set_html(&mut self, html: &str) -> cu::Result<()> {
+        cu::check!(self.inner.set_html(html, None), "failed to set clipboard")
+    }
+
+    fn set_image(&mut self, kind: &str, _data: &[u8]) -> cu::Result<()> {
+        // arboard only takes decoded RGBA pixels
+        cu::bail!("setting {kind} image is only supported on Windows");
+    }
+}
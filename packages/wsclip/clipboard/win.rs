@@ -0,0 +1,199 @@
+// SPDX-License-Identifier: MIT
+// Copyright (c) 2026 Pistonite
+
+use cu::pre::*;
+
+use clipboard_win::formats::{Bitmap, CF_DIB, CF_TEXT, Html, RawData, Unicode};
+use windows_sys::Win32::Globalization::{CP_ACP, WideCharToMultiByte};
+
+use super::{Clipboard, TextFormat};
+
+pub struct SystemClipboard {
+    format: TextFormat,
+}
+
+impl SystemClipboard {
+    pub fn new(format: TextFormat) -> cu::Result<Self> {
+        Ok(Self { format })
+    }
+}
+
+impl Clipboard for SystemClipboard {
+    fn set_text(&mut self, text: &str) -> cu::Result<()> {
+        let result = match self.format {
+            TextFormat::Unicode => clipboard_win::set_clipboard(Unicode, text),
+            TextFormat::Ansi => clipboard_win::set_clipboard(RawData(CF_TEXT), &to_ansi(text)?),
+        };
+        if let Err(ec) = result {
+            cu::bail!("failed to set clipboard: error code: {ec}");
+        }
+        Ok(())
+    }
+
+    fn get_text(&mut self) -> cu::Result<String> {
+        match clipboard_win::get_clipboard_string() {
+            Ok(text) => Ok(text),
+            Err(ec) => cu::bail!("failed to get clipboard as text: error code: {ec}"),
+        }
+    }
+
+    fn set_html(&mut self, html: &str) -> cu::Result<()> {
+        let Some(format) = Html::new() else {
+            cu::bail!("failed to register HTML clipboard format");
+        };
+        // the CF_HTML header is added by clipboard_win
+        if let Err(ec) = clipboard_win::set_clipboard(format, html) {
+            cu::bail!("failed to set clipboard: error code: {ec}");
+        }
+        Ok(())
+    }
+
+    fn set_image(&mut self, kind: &str, data: &[u8]) -> cu::Result<()> {
+        let result = match kind {
+            "image/png" => {
+                // PNG is not a standard format, so also set CF_DIB for apps that don't
+                // understand it. The PNG format is kept to preserve transparency
+                let dib = png_to_dib(data)?;
+                let Some(format) = clipboard_win::register_format("PNG") else {
+                    cu::bail!("failed to register PNG clipboard format");
+                };
+                clipboard_win::Clipboard::new_attempts(10).and_then(|_clip| {
+                    clipboard_win::raw::empty()?;
+                    clipboard_win::raw::set_without_clear(CF_DIB, &dib)?;
+                    clipboard_win::raw::set_without_clear(format.get(), data)
+                })
+            }
+            "image/bmp" => clipboard_win::set_clipboard(Bitmap, data),
+            "image/dib" => clipboard_win::set_clipboard(RawData(CF_DIB), data),
+            _ => cu::bail!("unsupported image format: {kind}"),
+        };
+        if let Err(ec) = result {
+            cu::bail!("failed to set clipboard: error code: {ec}");
+        }
+        Ok(())
+    }
+}
+
+/// Decode the PNG image and convert it to a 32-bit bottom-up DIB (`BITMAPINFOHEADER` followed by the BGRA pixels)
+fn png_to_dib(data: &[u8]) -> cu::Result<Vec<u8>> {
+    let mut decoder = png::Decoder::new(std::io::Cursor::new(data));
+    decoder.set_transformations(
+        png::Transformations::normalize_to_color8() | png::Transformations::ALPHA,
+    );
+    let mut reader = cu::check!(decoder.read_info(), "failed to decode PNG image")?;
+    let Some(size) = reader.output_buffer_size() else {
+        cu::bail!("PNG image is too large");
+    };
+    let mut pixels = vec![0u8; size];
+    let info = cu::check!(reader.next_frame(&mut pixels), "failed to decode PNG image")?;
+    let (Ok(width), Ok(height)) = (i32::try_from(info.width), i32::try_from(info.height)) else {
+        cu::bail!("PNG image is too large");
+    };
+    let image_size = info.width as usize * info.height as usize * 4;
+    let Ok(image_size_u32) = u32::try_from(image_size) else {
+        cu::bail!("PNG image is too large");
+    };
+    let mut dib = Vec::with_capacity(40 + image_size);
+    dib.extend_from_slice(&40u32.to_le_bytes()); // biSize
+    dib.extend_from_slice(&width.to_le_bytes());
+    dib.extend_from_slice(&height.to_le_bytes()); // positive height means bottom-up
+    dib.extend_from_slice(&1u16.to_le_bytes()); // biPlanes
+    dib.extend_from_slice(&32u16.to_le_bytes()); // biBitCount
+    dib.extend_from_slice(&0u32.to_le_bytes()); // biCompression = BI_RGB
+    dib.extend_from_slice(&image_size_u32.to_le_bytes());
+    dib.extend_from_slice(&[0; 16]); // resolution and color table sizes
+    // with the ALPHA transformation, the output is either RGBA or gray+alpha
+    for row in pixels[..info.buffer_size()]
+        .chunks_exact(info.line_size)
+        .rev()
+    {
+        match info.color_type {
+            png::ColorType::Rgba => {
+                for px in row.chunks_exact(4) {
+                    dib.extend_from_slice(&[px[2], px[1], px[0], px[3]]);
+                }
+            }
+            png::ColorType::GrayscaleAlpha => {
+                for px in row.chunks_exact(2) {
+                    dib.extend_from_slice(&[px[0], px[0], px[0], px[1]]);
+                }
+            }
+            other => cu::bail!("unexpected PNG output color type: {other:?}"),
+        }
+    }
+    Ok(dib)
+}
+
+/// Convert the text to a null-terminated string in the system code page.
+/// Characters not in the code page are replaced with the default character (usually `?`)
+fn to_ansi(text: &str) -> cu::Result<Vec<u8>> {
+    let wide = text.encode_utf16().chain(Some(0)).collect::<Vec<_>>();
+    let Ok(wide_len) = i32::try_from(wide.len()) else {
+        cu::bail!("text is too long");
+    };
+    // SAFETY: wide is valid for wide_len, and the output buffer is not used
+    let len = unsafe {
+        WideCharToMultiByte(
+            CP_ACP,
+            0,
+            wide.as_ptr(),
+            wide_len,
+            std::ptr::null_mut(),
+            0,
+            std::ptr::null(),
+            std::ptr::null_mut(),
+        )
+    };
+    if len <= 0 {
+        cu::bail!("failed to convert text to the system code page");
+    }
+    let mut out = vec![0u8; len as usize];
+    // SAFETY: out is valid for len bytes
+    let len = unsafe {
+        WideCharToMultiByte(
+            CP_ACP,
+            0,
+            wide.as_ptr(),
+            wide_len,
+            out.as_mut_ptr(),
+            len,
+            std::ptr::null(),
+            std::ptr::null_mut(),
+        )
+    };
+    if len <= 0 {
+        cu::bail!("failed to convert text to the system code page");
+    }
+    out.truncate(len as usize);
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_png_to_dib() {
+        // 1x2 image: red on top, half-transparent gray below
+        let mut png = Vec::new();
+        let mut encoder = png::Encoder::new(&mut png, 1, 2);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header().unwrap();
+        writer
+            .write_image_data(&[255, 0, 0, 255, 128, 128, 128, 64])
+            .unwrap();
+        writer.finish().unwrap();
+
+        let dib = png_to_dib(&png).unwrap();
+        assert_eq!(dib.len(), 40 + 8);
+        assert_eq!(&dib[0..4], &40u32.to_le_bytes());
+        assert_eq!(&dib[4..8], &1i32.to_le_bytes());
+        assert_eq!(&dib[8..12], &2i32.to_le_bytes());
+        assert_eq!(&dib[14..16], &32u16.to_le_bytes());
+        // bottom-up, BGRA
+        assert_eq!(&dib[40..], &[128, 128, 128, 64, 0, 0, 255, 255]);
+
+        assert!(png_to_dib(b"not a png").is_err());
+    }
+}
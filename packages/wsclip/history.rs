@@ -0,0 +1,84 @@
+// SPDX-License-Identifier: MIT
+// Copyright (c) 2026 Pistonite
+
+use std::collections::VecDeque;
+use std::time::SystemTime;
+
+use crate::log;
+
+/// The last copied texts, for `--history`
+pub struct History {
+    capacity: usize,
+    entries: VecDeque<Entry>,
+}
+
+struct Entry {
+    time: SystemTime,
+    id: usize,
+    text: String,
+}
+
+impl History {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            entries: VecDeque::with_capacity(capacity),
+        }
+    }
+
+    /// Remember the text copied from the connection, dropping the oldest entry if full
+    pub fn push(&mut self, id: usize, text: &str) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.entries.len() == self.capacity {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(Entry {
+            time: SystemTime::now(),
+            id,
+            text: text.to_string(),
+        });
+    }
+
+    /// Format the entries, newest first. Each entry is a `<time> <id> <length>` line,
+    /// followed by the text (`<length>` bytes) and a new line
+    pub fn format(&self) -> String {
+        let mut out = String::new();
+        for entry in self.entries.iter().rev() {
+            out.push_str(&format!(
+                "{} {} {}\n",
+                log::timestamp(entry.time),
+                entry.id,
+                entry.text.len()
+            ));
+            out.push_str(&entry.text);
+            out.push('\n');
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_history() {
+        let mut history = History::new(2);
+        history.push(1, "foo");
+        history.push(2, "bar\nbaz");
+        history.push(3, "qux");
+        let out = history.format();
+        let lines = out.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 5);
+        assert!(lines[0].ends_with(" 3 3"));
+        assert_eq!(lines[1], "qux");
+        assert!(lines[2].ends_with(" 2 7"));
+        assert_eq!(lines[3..], ["bar", "baz"]);
+
+        let mut history = History::new(0);
+        history.push(1, "foo");
+        assert_eq!(history.format(), "");
+    }
+}
@@ -0,0 +1,150 @@
+// SPDX-License-Identifier: MIT
+// Copyright (c) 2026 Pistonite
+
+//! Copy of the log output in a file, for `--log-file`
+
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use cu::pre::*;
+
+/// The log file is rotated to `<path>.1` when it grows over this size
+pub const MAX_SIZE: u64 = 10 * 1024 * 1024;
+
+static LOG_FILE: Mutex<Option<LogFile>> = Mutex::new(None);
+
+struct LogFile {
+    path: PathBuf,
+    file: File,
+    size: u64,
+}
+
+/// Start copying the log output to the file. The file is appended to if it exists
+pub fn init(path: &Path) -> cu::Result<()> {
+    let file = open(path)?;
+    let size = file.metadata().map(|x| x.len()).unwrap_or_default();
+    let log_file = LogFile {
+        path: path.to_path_buf(),
+        file,
+        size,
+    };
+    if let Ok(mut x) = LOG_FILE.lock() {
+        *x = Some(log_file);
+    }
+    Ok(())
+}
+
+fn open(path: &Path) -> cu::Result<File> {
+    cu::check!(
+        OpenOptions::new().create(true).append(true).open(path),
+        "failed to open log file '{}'",
+        path.display()
+    )
+}
+
+/// Write a line to the log file, if enabled
+pub fn write(level: &str, message: &str) {
+    let Ok(mut log_file) = LOG_FILE.lock() else {
+        return;
+    };
+    let Some(log_file) = log_file.as_mut() else {
+        return;
+    };
+    let line = format!("{} {level} {message}\n", timestamp(SystemTime::now()));
+    if log_file.size + line.len() as u64 > MAX_SIZE {
+        log_file.rotate();
+    }
+    // errors are ignored, since there's nowhere else to log them to
+    if log_file.file.write_all(line.as_bytes()).is_ok() {
+        log_file.size += line.len() as u64;
+    }
+}
+
+impl LogFile {
+    fn rotate(&mut self) {
+        let mut old = self.path.clone().into_os_string();
+        old.push(".1");
+        if let Err(e) = std::fs::rename(&self.path, &old) {
+            cu::warn!("failed to rotate log file: {e:?}");
+            return;
+        }
+        match open(&self.path) {
+            Ok(file) => {
+                self.file = file;
+                self.size = 0;
+            }
+            Err(e) => cu::warn!("{e:?}"),
+        }
+    }
+}
+
+/// Format the time as `YYYY-MM-DDTHH:MM:SSZ` in UTC
+pub fn timestamp(time: SystemTime) -> String {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .map(|x| x.as_secs())
+        .unwrap_or_default();
+    let (days, secs) = (secs / 86400, secs % 86400);
+    // days to civil date, see http://howardhinnant.github.io/date_algorithms.html
+    let z = days as i64 + 719468;
+    let era = z / 146097;
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z",
+        secs / 3600,
+        secs / 60 % 60,
+        secs % 60
+    )
+}
+
+/// Log with `cu::info!`, and also to the log file
+macro_rules! log_info {
+    ($($fmt:tt)*) => {{
+        let message = format!($($fmt)*);
+        cu::info!("{message}");
+        $crate::log::write("INFO", &message);
+    }};
+}
+pub(crate) use log_info as info;
+
+/// Log with `cu::warn!`, and also to the log file
+macro_rules! log_warn {
+    ($($fmt:tt)*) => {{
+        let message = format!($($fmt)*);
+        cu::warn!("{message}");
+        $crate::log::write("WARN", &message);
+    }};
+}
+pub(crate) use log_warn as warn;
+
+/// Log with `cu::error!`, and also to the log file
+macro_rules! log_error {
+    ($($fmt:tt)*) => {{
+        let message = format!($($fmt)*);
+        cu::error!("{message}");
+        $crate::log::write("ERROR", &message);
+    }};
+}
+pub(crate) use log_error as error;
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_timestamp() {
+        let time = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        assert_eq!(timestamp(time), "2023-11-14T22:13:20Z");
+        assert_eq!(timestamp(UNIX_EPOCH), "1970-01-01T00:00:00Z");
+    }
+}
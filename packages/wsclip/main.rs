@@ -1,12 +1,36 @@
 // SPDX-License-Identifier: MIT
 // Copyright (c) 2026 Pistonite
 
-#[cfg(not(windows))]
-compile_error!("this package can only be installed on windows");
-#[cfg(windows)]
-mod main_win;
-#[cfg(windows)]
+use cu::pre::*;
+
+mod clipboard;
+mod history;
+mod log;
+mod send;
+mod server;
+mod socket;
+
+/// Websocket server to put received messages into the clipboard
+#[derive(clap::Parser)]
+struct Cli {
+    #[clap(subcommand)]
+    command: Option<Command>,
+    #[clap(flatten)]
+    server: server::Cli,
+    #[clap(flatten)]
+    flags: cu::cli::Flags,
+}
+
+#[derive(clap::Subcommand)]
+enum Command {
+    /// Send the clipboard (or stdin) to a wsclip server
+    Send(send::Cli),
+}
+
 #[cu::cli(flags = "flags")]
-fn main(cli: main_win::Cli) -> cu::Result<()> {
-    main_win::run(cli)
+fn main(cli: Cli) -> cu::Result<()> {
+    match cli.command {
+        Some(Command::Send(args)) => send::run(args),
+        None => server::run(cli.server),
+    }
 }
@@ -0,0 +1,74 @@
+// SPDX-License-Identifier: MIT
+// Copyright (c) 2026 Pistonite
+
+use std::io::Read;
+
+use cu::pre::*;
+use tungstenite::client::IntoClientRequest;
+use tungstenite::http::HeaderValue;
+use tungstenite::protocol::frame::coding::CloseCode;
+use tungstenite::{Error as WsError, Message};
+
+use crate::clipboard::{self, TextFormat};
+use crate::server;
+
+#[derive(clap::Args)]
+pub struct Cli {
+    /// The server to send to, like `ws://192.168.0.2:8881`
+    #[clap(long)]
+    pub to: String,
+    /// Use `-` to send stdin instead of the clipboard
+    pub input: Option<String>,
+    /// The token to authenticate with, if the server requires one.
+    /// If not specified, the `WSCLIP_TOKEN` environment variable is used
+    #[clap(long)]
+    pub token: Option<String>,
+}
+
+pub fn run(cli: Cli) -> cu::Result<()> {
+    let text = match cli.input.as_deref() {
+        None => clipboard::open(TextFormat::Unicode)?.get_text()?,
+        Some("-") => {
+            let mut text = String::new();
+            cu::check!(
+                std::io::stdin().read_to_string(&mut text),
+                "failed to read stdin"
+            )?;
+            text
+        }
+        Some(x) => cu::bail!("unexpected input '{x}', use '-' to send stdin"),
+    };
+    let mut request = cu::check!(
+        cli.to.as_str().into_client_request(),
+        "invalid server address '{}'",
+        cli.to
+    )?;
+    if let Some(token) = server::resolve_token(&cli.token) {
+        let value = cu::check!(
+            HeaderValue::from_str(&format!("Bearer {token}")),
+            "invalid token"
+        )?;
+        request.headers_mut().insert("authorization", value);
+    }
+    let (mut ws, _) = cu::check!(
+        tungstenite::connect(request),
+        "failed to connect to '{}'",
+        cli.to
+    )?;
+    let len = text.len();
+    cu::check!(ws.send(Message::text(text)), "failed to send")?;
+    // wait for the server to close, so the message is not dropped
+    let _ = ws.close(None);
+    loop {
+        match ws.read() {
+            Ok(Message::Close(Some(frame))) if frame.code != CloseCode::Normal => {
+                cu::bail!("server closed the connection: {}", frame.reason);
+            }
+            Ok(_) => {}
+            Err(WsError::ConnectionClosed) | Err(WsError::AlreadyClosed) => break,
+            Err(e) => cu::bail!("connection closed unexpectedly: {e}"),
+        }
+    }
+    cu::info!("sent {len} bytes to {}", cli.to);
+    Ok(())
+}
@@ -0,0 +1,1257 @@
+// SPDX-License-Identifier: MIT
+// Copyright (c) 2026 Pistonite
+
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::io::{self, Write};
+use std::net::{IpAddr, Shutdown, SocketAddr};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, mpsc};
+use std::time::{Duration, Instant};
+
+use cu::pre::*;
+use tungstenite::handshake::server::{ErrorResponse, Request, Response};
+use tungstenite::http::StatusCode;
+use tungstenite::protocol::frame::Frame;
+use tungstenite::protocol::frame::coding::CloseCode;
+use tungstenite::protocol::{CloseFrame, WebSocketConfig};
+use tungstenite::{Error as WsError, Message, WebSocket};
+
+use crate::clipboard::{self, Clipboard, TextFormat};
+use crate::history::History;
+use crate::log;
+use crate::socket::{Address, Listener, Socket, Stream};
+
+/// Options for running the server
+#[derive(clap::Args)]
+#[group(id = "server")]
+pub struct Cli {
+    /// The port to open at
+    #[clap(short, long, default_value = "8881")]
+    pub port: u16,
+    /// The address to bind to. The default `0.0.0.0` allows computers in the same
+    /// network to connect. Use `127.0.0.1` to only allow local connections
+    #[clap(long, default_value = "0.0.0.0")]
+    pub bind: IpAddr,
+    /// Listen on a Unix domain socket at the path instead of TCP, for local-only use
+    #[clap(long, value_name = "PATH", conflicts_with_all = ["bind", "port"])]
+    pub unix: Option<PathBuf>,
+    /// Retry binding this many times (with 1 second between attempts), for example
+    /// when the port is still in use by the previous server that is exiting
+    #[clap(long, value_name = "N", default_value = "0")]
+    pub retry_bind: u32,
+    /// Accept secure websocket connections with TLS, using --cert and --key.
+    /// Clients must then connect with `wss://` instead of `ws://`
+    #[clap(long, requires_all = ["cert", "key"])]
+    pub tls: bool,
+    /// Path to the certificate (chain) in PEM format, for --tls
+    #[clap(long, requires = "tls")]
+    pub cert: Option<PathBuf>,
+    /// Path to the PKCS #8 private key in PEM format, for --tls
+    #[clap(long, requires = "tls")]
+    pub key: Option<PathBuf>,
+    /// Require clients to present the token, either with `?token=<token>` in the URL,
+    /// or with the `Authorization: Bearer <token>` header.
+    /// The token is also required for the health check (`GET /health`).
+    /// If not specified, the `WSCLIP_TOKEN` environment variable is used
+    #[clap(long)]
+    pub token: Option<String>,
+    /// Only accept connections from browsers with this `Origin` (like `https://example.com`).
+    /// Can be specified multiple times. Connections without the `Origin` header
+    /// (i.e. not from a browser) are not affected
+    #[clap(long, value_name = "ORIGIN")]
+    pub allow_origin: Vec<String>,
+    /// Maximum size of a message in bytes. Connections sending larger messages are closed
+    #[clap(long, value_name = "BYTES", default_value = "16777216")]
+    pub max_message: usize,
+    /// Send the clipboard back to clients when they request it with a `wsclip:get` message.
+    /// If the clipboard is not text, a `wsclip:error` message is sent instead
+    #[clap(long)]
+    pub serve: bool,
+    /// Remember the last N copied texts, which can be requested with a
+    /// `wsclip:history` message (requires `--serve`)
+    #[clap(long, value_name = "N", default_value = "0")]
+    pub history: usize,
+    /// Set the clipboard to the received text as-is.
+    ///
+    /// By default, the message is treated as a list of lines separated by null
+    /// characters (`\0`), which are converted to new lines, and invalid UTF-8 is
+    /// handled according to --decode. With --raw, null characters are kept, and
+    /// invalid UTF-8 is replaced with `U+FFFD` (unless `--decode strict`).
+    /// Note that on Windows, most apps stop reading the text at the first null character
+    #[clap(long)]
+    pub raw: bool,
+    /// Log a preview of the copied text (the first line, truncated).
+    /// Off by default, so the clipboard content is not logged
+    #[clap(long, overrides_with = "no_preview")]
+    pub preview: bool,
+    /// Don't log a preview of the copied text (default)
+    #[clap(long, overrides_with = "preview")]
+    pub no_preview: bool,
+    /// Set the clipboard even if the text is the same as the last one.
+    /// By default, the text is not set again if the clipboard still has it
+    #[clap(long)]
+    pub no_dedup: bool,
+    /// How to handle lines that are not valid UTF-8
+    #[clap(long, value_enum, default_value_t = DecodeMode::Partial)]
+    pub decode: DecodeMode,
+    /// The clipboard format to set text in
+    #[clap(long, value_enum, default_value_t = TextFormat::Unicode)]
+    pub format: TextFormat,
+    /// Send a ping to connections that are idle for this many seconds,
+    /// to detect dead connections
+    #[clap(long, value_name = "SECONDS")]
+    pub ping_interval: Option<u64>,
+    /// Close connections that don't respond to the ping in this many seconds
+    #[clap(
+        long,
+        value_name = "SECONDS",
+        default_value = "10",
+        requires = "ping_interval"
+    )]
+    pub ping_timeout: u64,
+    /// Refuse new connections when this many connections are open
+    #[clap(long, value_name = "N")]
+    pub max_connections: Option<usize>,
+    /// Limit how many times per second each connection can set the clipboard.
+    /// Updates over the limit are dropped
+    #[clap(long, value_name = "N")]
+    pub rate: Option<u32>,
+    /// Also write the log to the file, with timestamps. The file is rotated
+    /// to `<path>.1` when it grows over 10 MiB
+    #[clap(long, value_name = "PATH")]
+    pub log_file: Option<PathBuf>,
+}
+
+pub fn run(cli: Cli) -> cu::Result<()> {
+    if let Some(path) = &cli.log_file {
+        log::init(path)?;
+    }
+    let address = match &cli.unix {
+        #[cfg(unix)]
+        Some(path) => Address::Unix(path.clone()),
+        #[cfg(not(unix))]
+        Some(_) => cu::bail!("--unix is only supported on Unix"),
+        None => Address::Tcp(SocketAddr::new(cli.bind, cli.port)),
+    };
+    let clipboard = clipboard::open(cli.format)?;
+    let acceptor = if cli.tls {
+        Some(cu::check!(make_tls_acceptor(&cli), "failed to set up TLS")?)
+    } else {
+        None
+    };
+    let stats = Arc::new(Stats {
+        started: Instant::now(),
+        connections: AtomicUsize::new(0),
+        bytes_copied: AtomicU64::new(0),
+    });
+    let handshake = Handshake {
+        acceptor,
+        stats: Arc::clone(&stats),
+        token: resolve_token(&cli.token),
+        allow_origin: cli.allow_origin.clone(),
+        config: WebSocketConfig::default()
+            .max_message_size(Some(cli.max_message))
+            .max_frame_size(Some(cli.max_message)),
+    };
+    let server = bind_with_retry(&address, cli.retry_bind)?;
+    let scheme = if cli.tls { "wss" } else { "ws" };
+    log::info!("server started on {scheme}://{address}");
+    let handshake = Arc::new(handshake);
+    let ping = cli.ping_interval.map(|interval| Ping {
+        interval: Duration::from_secs(interval),
+        timeout: Duration::from_secs(cli.ping_timeout),
+    });
+    let (event_send, event_recv) = mpsc::channel();
+    let running = Arc::new(AtomicBool::new(true));
+    // the underlying sockets of open connections, to unblock
+    // the connection threads when shutting down
+    let streams = Arc::new(Mutex::new(HashMap::new()));
+    let address = Arc::new(address);
+    // ctrl-c handler
+    {
+        let running = Arc::clone(&running);
+        let event_send = event_send.clone();
+        let attempted = AtomicBool::new(false);
+        let address = Arc::clone(&address);
+        if let Err(e) = cu::cli::add_global_ctrlc_handler(move || {
+            // CAS probably not needed, just in case :)
+            if attempted
+                .compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst)
+                .is_ok()
+            {
+                log::warn!("requesting graceful termination... Ctrl-C again to force exit");
+                running.store(false, Ordering::Release);
+                let _ = event_send.send(Event::Shutdown);
+                // make a new connection to unblock server thread
+                if let Err(e) = address.connect_to_self() {
+                    log::error!("failed to make new connection to server: {e:?}");
+                    cu::hint!("Ctrl-C again to force exit");
+                }
+                return;
+            }
+            log::warn!("force exitting...");
+            std::process::exit(19937);
+        }) {
+            log::warn!("failed to set Ctrl-C handler, graceful termination is not possible: {e:?}");
+        }
+    }
+
+    // accepting thread, which spawns a thread for each connection
+    let accepting_thread = {
+        let running = Arc::clone(&running);
+        let streams = Arc::clone(&streams);
+        let event_send = event_send.clone();
+        let max_connections = cli.max_connections;
+        let refusing = Arc::new(AtomicUsize::new(0));
+        std::thread::spawn(move || {
+            let mut id = 1;
+            loop {
+                let stream = server.accept();
+                if !running.load(Ordering::Acquire) {
+                    return;
+                }
+                let stream = match stream {
+                    Err(e) => {
+                        log::error!("failed to accept new connection: {e:?}");
+                        continue;
+                    }
+                    Ok(x) => x,
+                };
+                if let Some(max) = max_connections {
+                    let count = streams.lock().map(|x| x.len()).unwrap_or_default();
+                    if count >= max {
+                        let peer = stream.peer_addr();
+                        log::warn!("too many connections ({max}), refusing connection from {peer}");
+                        // refuse on a separate thread so slow clients don't block accepting,
+                        // but don't let the threads pile up
+                        if refusing.fetch_add(1, Ordering::AcqRel) >= MAX_REFUSING {
+                            refusing.fetch_sub(1, Ordering::AcqRel);
+                            cu::debug!("dropping connection from {peer} without the close frame");
+                            continue;
+                        }
+                        let handshake = Arc::clone(&handshake);
+                        let refusing = Arc::clone(&refusing);
+                        std::thread::spawn(move || {
+                            handshake.refuse(stream);
+                            refusing.fetch_sub(1, Ordering::AcqRel);
+                        });
+                        continue;
+                    }
+                }
+                let stream_clone = match stream.try_clone() {
+                    Err(e) => {
+                        log::error!("failed to accept new connection: {e:?}");
+                        continue;
+                    }
+                    Ok(x) => x,
+                };
+                if let Ok(mut streams) = streams.lock() {
+                    streams.insert(id, stream_clone);
+                }
+                let handshake = Arc::clone(&handshake);
+                let running = Arc::clone(&running);
+                let streams = Arc::clone(&streams);
+                let event_send = event_send.clone();
+                std::thread::spawn(move || {
+                    if let Some(ws) = handshake.accept(stream) {
+                        handshake.stats.connections.fetch_add(1, Ordering::Relaxed);
+                        serve_connection(id, ws, ping, &event_send, &running);
+                        handshake.stats.connections.fetch_sub(1, Ordering::Relaxed);
+                    }
+                    if let Ok(mut streams) = streams.lock() {
+                        streams.remove(&id);
+                    }
+                    let _ = event_send.send(Event::Closed(id));
+                });
+                id += 1;
+            }
+        })
+    };
+
+    // main loop, which owns the clipboard
+    let mut handler = Handler {
+        cli: &cli,
+        clipboard,
+        history: History::new(cli.history),
+        rates: HashMap::new(),
+        stats,
+        last_text: None,
+    };
+    for event in &event_recv {
+        match event {
+            Event::Message { id, message, reply } => {
+                let mut conn = Conn {
+                    id,
+                    replies: vec![],
+                };
+                handler.handle_message(&mut conn, &message);
+                let _ = reply.send(conn.replies);
+            }
+            Event::Closed(id) => {
+                cu::debug!("[{id}] connection thread finished");
+                handler.rates.remove(&id);
+            }
+            Event::Shutdown => break,
+        }
+    }
+
+    // no new connections after the accepting thread is finished
+    let _ = accepting_thread.join();
+    // stop reading from the connections, so the connection threads
+    // wake up, send the close frame and finish
+    let mut remaining = HashSet::new();
+    if let Ok(streams) = streams.lock() {
+        for (id, stream) in streams.iter() {
+            let _ = stream.shutdown(Shutdown::Read);
+            remaining.insert(*id);
+        }
+    }
+    wait_for_closed(&event_recv, &mut remaining, SHUTDOWN_TIMEOUT);
+    if !remaining.is_empty() {
+        cu::debug!("{} connection(s) did not close in time", remaining.len());
+        if let Ok(streams) = streams.lock() {
+            for id in &remaining {
+                if let Some(stream) = streams.get(id) {
+                    let _ = stream.shutdown(Shutdown::Both);
+                }
+            }
+        }
+        wait_for_closed(&event_recv, &mut remaining, SHUTDOWN_TIMEOUT);
+    }
+    address.cleanup();
+    log::info!("server closed");
+    cu::lv::disable_print_time();
+    Ok(())
+}
+
+/// Maximum number of connections being refused (for --max-connections) at the same time.
+/// More connections are dropped without sending the close frame
+const MAX_REFUSING: usize = 8;
+
+/// How long to wait for the client when accepting a connection,
+/// so clients that connect and stay silent don't keep the thread around
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How long to wait for the connections to close when shutting down
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Wait for the connection threads in `remaining` to finish, until the timeout.
+///
+/// Messages are dropped without replying, which drops the reply channel,
+/// so the threads stop waiting for the reply
+fn wait_for_closed(
+    event_recv: &mpsc::Receiver<Event>,
+    remaining: &mut HashSet<usize>,
+    timeout: Duration,
+) {
+    let deadline = Instant::now() + timeout;
+    while !remaining.is_empty() {
+        let timeout = deadline.saturating_duration_since(Instant::now());
+        match event_recv.recv_timeout(timeout) {
+            Ok(Event::Closed(id)) => {
+                remaining.remove(&id);
+            }
+            Ok(_) => {}
+            Err(_) => break,
+        }
+    }
+}
+
+fn bind_with_retry(address: &Address, retries: u32) -> cu::Result<Listener> {
+    let mut attempt = 0;
+    loop {
+        match address.bind() {
+            Ok(x) => return Ok(x),
+            Err(e) if attempt < retries => {
+                attempt += 1;
+                log::warn!("failed to bind to {address}, retrying ({attempt}/{retries})...");
+                cu::debug!("{e:?}");
+                std::thread::sleep(Duration::from_secs(1));
+            }
+            Err(e) if retries > 0 => {
+                log::error!("failed to bind to {address} after {} attempts", retries + 1);
+                return Err(e);
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Options for pinging idle connections, for --ping-interval
+#[derive(Clone, Copy)]
+struct Ping {
+    interval: Duration,
+    timeout: Duration,
+}
+
+/// Events sent to the main loop
+enum Event {
+    /// A text or binary message is received. The replies to send back
+    /// to the client are sent to the `reply` channel, which is created
+    /// for each message
+    Message {
+        id: usize,
+        message: Vec<u8>,
+        reply: mpsc::Sender<Vec<Message>>,
+    },
+    /// The connection thread is finished
+    Closed(usize),
+    /// Ctrl-C is pressed
+    Shutdown,
+}
+
+/// Read messages from the connection until it's closed
+fn serve_connection(
+    id: usize,
+    mut ws: WebSocket<Stream>,
+    ping: Option<Ping>,
+    event_send: &mpsc::Sender<Event>,
+    running: &AtomicBool,
+) {
+    if let Some(ping) = ping {
+        // wake up from reading periodically to check if ping is needed
+        let timeout = ping.interval.min(ping.timeout).max(Duration::from_secs(1));
+        if let Err(e) = ws.get_ref().socket().set_read_timeout(Some(timeout)) {
+            log::warn!("[{id}] failed to set read timeout, ping is disabled: {e:?}");
+        }
+    }
+    let mut last_seen = Instant::now();
+    let mut ping_sent: Option<Instant> = None;
+    // if the loop stopped because the server is shutting down
+    let mut shutting_down = false;
+    loop {
+        let result = ws.read();
+        if result.is_ok() {
+            last_seen = Instant::now();
+            ping_sent = None;
+        }
+        let message = match result {
+            Ok(Message::Text(bytes)) => bytes.as_bytes().to_vec(),
+            Ok(Message::Binary(bytes)) => bytes.to_vec(),
+            Ok(msg) => {
+                cu::debug!("[{id}] received: {msg:?}");
+                continue;
+            }
+            Err(WsError::Io(e))
+                if matches!(
+                    e.kind(),
+                    io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                ) =>
+            {
+                // read timed out, see if we need to ping
+                let Some(ping) = ping else {
+                    continue;
+                };
+                if let Some(sent) = ping_sent {
+                    if sent.elapsed() >= ping.timeout {
+                        log::warn!("[{id}] ping timed out");
+                        break;
+                    }
+                    continue;
+                }
+                if last_seen.elapsed() >= ping.interval {
+                    cu::debug!("[{id}] sending ping");
+                    if let Err(e) = ws.send(Message::Ping(Default::default())) {
+                        log::error!("[{id}] failed to send ping: {e:?}");
+                        break;
+                    }
+                    ping_sent = Some(Instant::now());
+                }
+                continue;
+            }
+            Err(WsError::ConnectionClosed) | Err(WsError::AlreadyClosed) => break,
+            Err(WsError::Capacity(e)) => {
+                log::warn!("[{id}] message rejected: {e}");
+                let frame = CloseFrame {
+                    code: CloseCode::Size,
+                    reason: "message too large".into(),
+                };
+                let _ = ws.close(Some(frame));
+                continue;
+            }
+            Err(WsError::Io(e))
+                if matches!(
+                    e.kind(),
+                    io::ErrorKind::ConnectionReset
+                        | io::ErrorKind::ConnectionRefused
+                        | io::ErrorKind::ConnectionAborted
+                ) =>
+            {
+                break;
+            }
+            Err(e) => {
+                // the stream is shut down when closing the server
+                if !running.load(Ordering::Acquire) {
+                    shutting_down = true;
+                    break;
+                }
+                log::error!("[{id}] read error: {e:?}");
+                // send a close frame
+                let _ = ws.close(None);
+                let _ = ws.flush();
+                break;
+            }
+        };
+        let Some(replies) = request(id, message, event_send) else {
+            shutting_down = true;
+            break;
+        };
+        for reply in replies {
+            if let Err(e) = ws.send(reply) {
+                log::error!("[{id}] failed to send message: {e:?}");
+            }
+        }
+    }
+    if shutting_down {
+        send_shutdown_close_frame(ws.into_inner());
+        return;
+    }
+    if running.load(Ordering::Acquire) {
+        // don't print closed message after closing
+        log::info!("[{id}] closed");
+    }
+}
+
+/// Tell the client the server is shutting down.
+///
+/// The stream is shut down for reading at this point, which tungstenite treats
+/// as a reset connection and refuses to send the close frame, so it's written directly
+fn send_shutdown_close_frame(mut stream: Stream) {
+    let frame = CloseFrame {
+        code: CloseCode::Away,
+        reason: "server is shutting down".into(),
+    };
+    let mut buf = vec![];
+    if Frame::close(Some(frame)).format(&mut buf).is_err() {
+        return;
+    }
+    let _ = stream.write_all(&buf);
+    let _ = stream.flush();
+    let _ = stream.socket().shutdown(Shutdown::Write);
+}
+
+/// Send the message to the main loop and wait for the replies.
+/// Returns `None` if the main loop dropped the message, when the server is shutting down
+fn request(id: usize, message: Vec<u8>, event_send: &mpsc::Sender<Event>) -> Option<Vec<Message>> {
+    let (reply_send, reply_recv) = mpsc::channel();
+    let event = Event::Message {
+        id,
+        message,
+        reply: reply_send,
+    };
+    event_send.send(event).ok()?;
+    reply_recv.recv().ok()
+}
+
+/// Options for accepting new connections
+struct Handshake {
+    acceptor: Option<native_tls::TlsAcceptor>,
+    stats: Arc<Stats>,
+    token: Option<String>,
+    allow_origin: Vec<String>,
+    config: WebSocketConfig,
+}
+
+impl Handshake {
+    /// Do the TLS and websocket handshake, and check the origin and token
+    fn accept(&self, stream: Socket) -> Option<WebSocket<Stream>> {
+        let peer = stream.peer_addr();
+        if let Err(e) = stream.set_read_timeout(Some(HANDSHAKE_TIMEOUT)) {
+            log::error!("failed to set read timeout for connection from {peer}: {e:?}");
+            return None;
+        }
+        let mut stream = match &self.acceptor {
+            None => Stream::plain(stream),
+            Some(acceptor) => match acceptor.accept(stream) {
+                Err(e) => {
+                    log::error!("failed to accept new TLS connection from {peer}: {e:?}");
+                    return None;
+                }
+                Ok(x) => Stream::tls(x),
+            },
+        };
+        match stream.peek_request_head(MAX_REQUEST_HEAD) {
+            Err(e) => {
+                log::error!("failed to read request from {peer}: {e:?}");
+                return None;
+            }
+            Ok(head) if is_health_request(head) => {
+                if let Some(token) = &self.token
+                    && !head_has_token(head, token)
+                {
+                    log::warn!("rejected health check from {peer}: missing or invalid token");
+                    let _ = stream.write_all(
+                        b"HTTP/1.1 401 Unauthorized\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                    );
+                    let _ = stream.flush();
+                    return None;
+                }
+                cu::debug!("health check from {peer}");
+                self.serve_health(stream);
+                return None;
+            }
+            Ok(_) => {}
+        }
+        let mut authorized = self.token.is_none();
+        let mut rejected_origin = None;
+        // the error type is required by tungstenite
+        #[allow(clippy::result_large_err)]
+        let callback = |request: &Request, response: Response| {
+            if let Some(origin) = self.check_origin(request) {
+                rejected_origin = Some(origin.to_string());
+                let mut response = ErrorResponse::new(Some("origin not allowed".to_string()));
+                *response.status_mut() = StatusCode::FORBIDDEN;
+                return Err(response);
+            }
+            if let Some(token) = &self.token {
+                authorized = request_has_token(request, token);
+            }
+            Ok(response)
+        };
+        let mut ws = match tungstenite::accept_hdr_with_config(stream, callback, Some(self.config))
+        {
+            Err(e) => {
+                // the error holds the callback, so format it before using rejected_origin
+                let e = format!("{e:?}");
+                match rejected_origin {
+                    Some(origin) => {
+                        log::warn!("rejected connection from {peer}: origin not allowed: {origin}")
+                    }
+                    None => {
+                        log::error!("failed to accept new websocket connection from {peer}: {e}")
+                    }
+                }
+                return None;
+            }
+            Ok(x) => x,
+        };
+        if !authorized {
+            log::warn!("rejected connection from {peer}: missing or invalid token");
+            close_with_reason(&mut ws, CloseCode::Policy, "missing or invalid token");
+            return None;
+        }
+        if let Err(e) = ws.get_ref().socket().set_read_timeout(None) {
+            log::error!("failed to reset read timeout for connection from {peer}: {e:?}");
+            return None;
+        }
+        cu::debug!("accepted connection from {peer}");
+        Some(ws)
+    }
+
+    /// Respond to `GET /health` with the status in JSON
+    fn serve_health(&self, mut stream: Stream) {
+        let body = format!(
+            r#"{{"status":"ok","uptime":{},"connections":{},"bytes_copied":{}}}"#,
+            self.stats.started.elapsed().as_secs(),
+            self.stats.connections.load(Ordering::Relaxed),
+            self.stats.bytes_copied.load(Ordering::Relaxed),
+        );
+        let response = format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+            body.len()
+        );
+        let _ = stream.write_all(response.as_bytes());
+        let _ = stream.flush();
+    }
+
+    /// Tell the client to try again later, for --max-connections
+    fn refuse(&self, stream: Socket) {
+        if let Some(mut ws) = self.accept(stream) {
+            // don't wait forever for the client to acknowledge
+            let _ = ws
+                .get_ref()
+                .socket()
+                .set_read_timeout(Some(HANDSHAKE_TIMEOUT));
+            close_with_reason(
+                &mut ws,
+                CloseCode::Again,
+                "too many connections, try again later",
+            );
+        }
+    }
+
+    /// Return the origin of the request if it's not allowed
+    fn check_origin<'a>(&self, request: &'a Request) -> Option<&'a str> {
+        if self.allow_origin.is_empty() {
+            return None;
+        }
+        let origin = request.headers().get("origin")?;
+        let origin = origin.to_str().unwrap_or("<invalid>");
+        if self.allow_origin.iter().any(|x| origin_eq(x, origin)) {
+            return None;
+        }
+        Some(origin)
+    }
+}
+
+/// Max size of the HTTP request head to read when checking for health requests.
+/// Larger requests are left for the websocket handshake to reject
+const MAX_REQUEST_HEAD: usize = 8192;
+
+/// Check if the request is `GET /health` (and not a websocket upgrade)
+fn is_health_request(head: &[u8]) -> bool {
+    let Ok(head) = str::from_utf8(head) else {
+        return false;
+    };
+    let mut lines = head.lines();
+    let mut request_line = lines.next().unwrap_or_default().split_whitespace();
+    if request_line.next() != Some("GET") {
+        return false;
+    }
+    let path = request_line.next().unwrap_or_default();
+    let path = path.split('?').next().unwrap_or_default();
+    if path != "/health" {
+        return false;
+    }
+    !lines
+        .take_while(|x| !x.is_empty())
+        .filter_map(|x| x.split_once(':'))
+        .any(|(name, _)| name.trim().eq_ignore_ascii_case("upgrade"))
+}
+
+/// Server status, for the health endpoint
+struct Stats {
+    started: Instant,
+    /// Number of open websocket connections
+    connections: AtomicUsize,
+    bytes_copied: AtomicU64,
+}
+
+/// Close the connection with the code and reason
+fn close_with_reason(ws: &mut WebSocket<Stream>, code: CloseCode, reason: &str) {
+    let frame = CloseFrame {
+        code,
+        reason: reason.into(),
+    };
+    let _ = ws.close(Some(frame));
+    // wait for the client to acknowledge, so it receives the close frame
+    // instead of a connection reset
+    while ws.read().is_ok() {}
+}
+
+/// Origins are case-insensitive, and the trailing slash is ignored
+fn origin_eq(a: &str, b: &str) -> bool {
+    a.trim_end_matches('/')
+        .eq_ignore_ascii_case(b.trim_end_matches('/'))
+}
+
+/// Environment variable for the token, so it doesn't show up in the command line
+const TOKEN_ENV: &str = "WSCLIP_TOKEN";
+
+/// Get the token from the command line, or from the environment if not specified
+pub fn resolve_token(token: &Option<String>) -> Option<String> {
+    if token.is_some() {
+        return token.clone();
+    }
+    cu::env_var(TOKEN_ENV).ok().filter(|x| !x.is_empty())
+}
+
+/// Check the token in the query string or the Authorization header
+fn request_has_token(request: &Request, token: &str) -> bool {
+    let from_query = request
+        .uri()
+        .query()
+        .into_iter()
+        .flat_map(|x| x.split('&'))
+        .filter_map(|x| x.strip_prefix("token="));
+    let from_header = request
+        .headers()
+        .get_all("authorization")
+        .into_iter()
+        .filter_map(|x| x.to_str().ok()?.strip_prefix("Bearer "));
+    from_query
+        .chain(from_header)
+        .any(|x| constant_time_eq(x.trim(), token))
+}
+
+/// Check the token in the raw request head, see [`request_has_token`]
+fn head_has_token(head: &[u8], token: &str) -> bool {
+    let Ok(head) = str::from_utf8(head) else {
+        return false;
+    };
+    let mut lines = head.lines();
+    let uri = lines
+        .next()
+        .unwrap_or_default()
+        .split_whitespace()
+        .nth(1)
+        .unwrap_or_default();
+    let mut builder = Request::builder().uri(uri);
+    for (name, value) in lines
+        .take_while(|x| !x.is_empty())
+        .filter_map(|x| x.split_once(':'))
+    {
+        builder = builder.header(name.trim(), value.trim());
+    }
+    builder
+        .body(())
+        .is_ok_and(|request| request_has_token(&request, token))
+}
+
+/// Compare without returning early, so the token can't be guessed from the timing
+fn constant_time_eq(a: &str, b: &str) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.bytes()
+        .zip(b.bytes())
+        .fold(0, |acc, (x, y)| acc | (x ^ y))
+        == 0
+}
+
+fn make_tls_acceptor(cli: &Cli) -> cu::Result<native_tls::TlsAcceptor> {
+    let (Some(cert), Some(key)) = (&cli.cert, &cli.key) else {
+        cu::bail!("--cert and --key are required for --tls");
+    };
+    let cert = cu::check!(
+        std::fs::read(cert),
+        "failed to read certificate '{}'",
+        cert.display()
+    )?;
+    let key = cu::check!(
+        std::fs::read(key),
+        "failed to read private key '{}'",
+        key.display()
+    )?;
+    let identity = cu::check!(
+        native_tls::Identity::from_pkcs8(&cert, &key),
+        "failed to load certificate and private key"
+    )?;
+    let acceptor = cu::check!(
+        native_tls::TlsAcceptor::new(identity),
+        "failed to create TLS acceptor"
+    )?;
+    Ok(acceptor)
+}
+
+/// Messages that start with this prefix are commands instead of clipboard content.
+///
+/// The prefix is followed by the command name, and optionally a new line and the data:
+/// - `wsclip:get`: request the clipboard, requires `--serve`
+/// - `wsclip:history`: request the history (see [`History::format`]), requires `--serve`
+/// - `wsclip:error\n<message>`: sent to the client when the request failed
+/// - `wsclip:text/html\n<html>`: set the clipboard to the HTML fragment (as `CF_HTML` on Windows)
+/// - `wsclip:image/png\n<data>`: set the clipboard to the PNG image (decoded as `CF_DIB`,
+///   and also as the `PNG` format for apps that support transparency)
+/// - `wsclip:image/bmp\n<data>`: set the clipboard to the BMP file (as `CF_BITMAP`)
+/// - `wsclip:image/dib\n<data>`: set the clipboard to the DIB (as `CF_DIB`)
+///
+/// Images are only supported on Windows
+const HEADER_PREFIX: &[u8] = b"wsclip:";
+
+/// Split the message into the command and the data, if it's a command
+fn parse_header(bytes: &[u8]) -> Option<(&str, &[u8])> {
+    let rest = bytes.strip_prefix(HEADER_PREFIX)?;
+    let (command, data) = match rest.iter().position(|x| *x == b'\n') {
+        Some(i) => (&rest[..i], &rest[i + 1..]),
+        None => (rest, &[][..]),
+    };
+    Some((str::from_utf8(command).ok()?.trim(), data))
+}
+
+/// Handles the messages on the main loop
+struct Handler<'a> {
+    cli: &'a Cli,
+    clipboard: Box<dyn Clipboard>,
+    history: History,
+    /// Rate limit of each connection, for --rate
+    rates: HashMap<usize, RateLimit>,
+    stats: Arc<Stats>,
+    /// The last text set to the clipboard, for skipping unchanged updates
+    last_text: Option<String>,
+}
+
+impl Handler<'_> {
+    fn handle_message(&mut self, conn: &mut Conn, bytes: &[u8]) {
+        let id = conn.id;
+        let header = parse_header(bytes);
+        let is_set = match header {
+            None => true,
+            Some((command, _)) => command == "text/html" || command.starts_with("image/"),
+        };
+        if is_set && !self.check_rate(id) {
+            return;
+        }
+        match header {
+            None => self.set_clipboard_bytes(id, bytes),
+            Some(("get", _)) => {
+                if !self.check_serve(conn, "clipboard") {
+                    return;
+                }
+                match self.clipboard.get_text() {
+                    Ok(text) => {
+                        log::info!("[{id}] sending clipboard ({} bytes)", text.len());
+                        conn.replies.push(Message::text(text));
+                    }
+                    Err(e) => {
+                        log::warn!("[{id}] {e:?}");
+                        send_error(conn, "the clipboard does not contain text");
+                    }
+                }
+            }
+            Some(("history", _)) => {
+                if !self.check_serve(conn, "history") {
+                    return;
+                }
+                log::info!("[{id}] sending history");
+                let message = format!("wsclip:history\n{}", self.history.format());
+                conn.replies.push(Message::text(message));
+            }
+            Some(("text/html", data)) => {
+                if let Err(e) = self.set_clipboard_html(id, data) {
+                    log::error!("[{id}] failed to set clipboard: {e:?}");
+                }
+            }
+            Some((kind, data)) if kind.starts_with("image/") => {
+                if let Err(e) = self.set_clipboard_image(id, kind, data) {
+                    log::error!("[{id}] failed to set clipboard: {e:?}");
+                }
+            }
+            Some((command, _)) => {
+                log::warn!("[{id}] unknown command: {command}");
+                send_error(conn, &format!("unknown command: {command}"));
+            }
+        }
+    }
+
+    /// Check if the text is the same as the last one, and the clipboard still has it
+    fn is_unchanged(&mut self, text: &str) -> bool {
+        if self.last_text.as_deref() != Some(text) {
+            return false;
+        }
+        // the clipboard could be changed by something else since
+        match self.clipboard.get_text() {
+            Ok(current) => current == text,
+            Err(_) => false,
+        }
+    }
+
+    fn add_bytes_copied(&self, len: usize) {
+        self.stats
+            .bytes_copied
+            .fetch_add(len as u64, Ordering::Relaxed);
+    }
+
+    /// Check if the connection can set the clipboard now, for --rate
+    fn check_rate(&mut self, id: usize) -> bool {
+        let Some(limit) = self.cli.rate else {
+            return true;
+        };
+        let rate = self.rates.entry(id).or_insert_with(RateLimit::new);
+        if rate.allow(Instant::now(), limit) {
+            return true;
+        }
+        if rate.dropped == 1 {
+            log::warn!("[{id}] throttled: more than {limit} updates per second, dropping");
+        }
+        false
+    }
+
+    /// Check if --serve is enabled, and send an error if not
+    fn check_serve(&self, conn: &mut Conn, what: &str) -> bool {
+        if !self.cli.serve {
+            log::warn!("[{}] requested {what}, but --serve is not enabled", conn.id);
+            send_error(conn, "the server is not serving the clipboard");
+        }
+        self.cli.serve
+    }
+
+    fn set_clipboard_html(&mut self, id: usize, data: &[u8]) -> cu::Result<()> {
+        cu::debug!("[{id}] received HTML, {} bytes", data.len());
+        let html = cu::check!(str::from_utf8(data), "HTML is not valid UTF-8")?;
+        self.last_text = None;
+        self.clipboard.set_html(html)?;
+        self.add_bytes_copied(html.len());
+        log::info!("[{id}] copied HTML ({} bytes)", html.len());
+        Ok(())
+    }
+
+    fn set_clipboard_image(&mut self, id: usize, kind: &str, data: &[u8]) -> cu::Result<()> {
+        cu::debug!("[{id}] received {kind} image, {} bytes", data.len());
+        self.last_text = None;
+        self.clipboard.set_image(kind, data)?;
+        self.add_bytes_copied(data.len());
+        log::info!("[{id}] copied {kind} image ({} bytes)", data.len());
+        Ok(())
+    }
+
+    fn set_clipboard_bytes(&mut self, id: usize, bytes: &[u8]) {
+        if let Err(e) = self.set_clipboard_bytes_internal(id, bytes) {
+            log::error!("[{id}] failed to set clipboard: {e:?}");
+        }
+    }
+    fn set_clipboard_bytes_internal(&mut self, id: usize, bytes: &[u8]) -> cu::Result<()> {
+        cu::debug!("[{id}] received {} bytes", bytes.len());
+        let utf8_content = if self.cli.raw {
+            decode_bytes_raw(id, bytes, self.cli.decode)?
+        } else {
+            decode_bytes(id, bytes, self.cli.decode)?
+        };
+        if self.is_unchanged(&utf8_content) {
+            cu::debug!("[{id}] unchanged");
+            return Ok(());
+        }
+        cu::debug!("[{id}] decoded {} bytes, copying...", utf8_content.len());
+        self.last_text = None;
+        self.clipboard.set_text(&utf8_content)?;
+        if !self.cli.no_dedup {
+            self.last_text = Some(utf8_content.clone());
+        }
+        self.add_bytes_copied(utf8_content.len());
+        if self.cli.preview {
+            log::info!("[{id}] copied: {}", preview(&utf8_content));
+        }
+        self.history.push(id, &utf8_content);
+        Ok(())
+    }
+}
+
+/// Number of updates in the current one-second window
+struct RateLimit {
+    start: Instant,
+    count: u32,
+    dropped: u32,
+}
+
+impl RateLimit {
+    fn new() -> Self {
+        Self {
+            start: Instant::now(),
+            count: 0,
+            dropped: 0,
+        }
+    }
+
+    fn allow(&mut self, now: Instant, limit: u32) -> bool {
+        if now.duration_since(self.start) >= Duration::from_secs(1) {
+            self.start = now;
+            self.count = 0;
+            self.dropped = 0;
+        }
+        if self.count < limit {
+            self.count += 1;
+            return true;
+        }
+        self.dropped += 1;
+        false
+    }
+}
+
+fn send_error(conn: &mut Conn, message: &str) {
+    let message = format!("wsclip:error\n{message}");
+    conn.replies.push(Message::text(message));
+}
+
+/// Max number of characters to show in the preview
+const PREVIEW_LEN: usize = 40;
+
+/// Get the first line of the text, truncated, for --preview
+fn preview(text: &str) -> String {
+    let mut lines = text.lines();
+    let first = lines.next().unwrap_or_default();
+    let mut preview = first.chars().take(PREVIEW_LEN).collect::<String>();
+    if preview.len() < first.len() || lines.next().is_some() {
+        preview.push_str("...");
+    }
+    // escape control characters
+    format!("{preview:?}")
+}
+
+/// How to handle invalid UTF-8, for --decode
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum DecodeMode {
+    /// Skip the lines that are not valid UTF-8, and copy the rest
+    Partial,
+    /// Replace invalid UTF-8 with `U+FFFD`
+    Lossy,
+    /// Don't set the clipboard if any line is not valid UTF-8
+    Strict,
+}
+
+/// Decode the bytes as UTF-8 without converting null characters, for --raw
+fn decode_bytes_raw(id: usize, bytes: &[u8], mode: DecodeMode) -> cu::Result<String> {
+    let utf8_content = match mode {
+        DecodeMode::Strict => match str::from_utf8(bytes) {
+            Ok(s) => s.to_string(),
+            Err(e) => cu::bail!("message is not valid UTF-8: {e}"),
+        },
+        DecodeMode::Partial | DecodeMode::Lossy => String::from_utf8_lossy(bytes).into_owned(),
+    };
+    log::info!("[{id}] decoded {} bytes (raw)", utf8_content.len());
+    Ok(utf8_content)
+}
+
+/// Decode the null-separated lines, converting each null character to a new line
+fn decode_bytes(id: usize, mut bytes: &[u8], mode: DecodeMode) -> cu::Result<String> {
+    let mut line_count = 0;
+    let mut utf8_content = String::new();
+    loop {
+        if bytes.is_empty() {
+            break;
+        }
+        match bytes.iter().position(|x| *x == 0) {
+            Some(null_i) => {
+                if null_i == 0 {
+                    utf8_content.push('\n');
+                    bytes = &bytes[1..];
+                    continue;
+                }
+                line_count += 1;
+                if let Some(s) = decode_line(id, line_count, &bytes[..null_i], mode)? {
+                    utf8_content.push_str(&s);
+                    utf8_content.push('\n');
+                }
+                bytes = &bytes[null_i + 1..];
+            }
+            None => {
+                line_count += 1;
+                if let Some(s) = decode_line(id, line_count, bytes, mode)? {
+                    utf8_content.push_str(&s);
+                }
+                break;
+            }
+        }
+    }
+    log::info!(
+        "[{id}] decoded {line_count} lines ({} bytes)",
+        utf8_content.len()
+    );
+    Ok(utf8_content)
+}
+
+/// Decode one line, returning `None` if the line should be skipped
+fn decode_line(
+    id: usize,
+    line_number: usize,
+    line: &[u8],
+    mode: DecodeMode,
+) -> cu::Result<Option<Cow<'_, str>>> {
+    let e = match str::from_utf8(line) {
+        Ok(s) => return Ok(Some(Cow::Borrowed(s))),
+        Err(e) => e,
+    };
+    match mode {
+        DecodeMode::Partial => {
+            log::error!("[{id}] failed to decode line {line_number}: {e:?}");
+            Ok(None)
+        }
+        DecodeMode::Lossy => {
+            log::warn!("[{id}] line {line_number} is not valid UTF-8, decoding lossily");
+            Ok(Some(String::from_utf8_lossy(line)))
+        }
+        DecodeMode::Strict => cu::bail!("line {line_number} is not valid UTF-8: {e}"),
+    }
+}
+
+/// A connection, as seen by the main loop
+struct Conn {
+    id: usize,
+    /// Messages to send back to the client
+    replies: Vec<Message>,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    #[test]
+    fn test_decode() -> cu::Result<()> {
+        let bytes = b"foo\0\0bar";
+        assert_eq!(decode_bytes(0, bytes, DecodeMode::Partial)?, "foo\n\nbar");
+        assert_eq!(
+            decode_bytes_raw(0, bytes, DecodeMode::Partial)?,
+            "foo\0\0bar"
+        );
+        let bytes = b"foo\0b\xffr\0baz";
+        assert_eq!(decode_bytes(0, bytes, DecodeMode::Partial)?, "foo\nbaz");
+        assert_eq!(
+            decode_bytes(0, bytes, DecodeMode::Lossy)?,
+            "foo\nb\u{FFFD}r\nbaz"
+        );
+        assert!(decode_bytes(0, bytes, DecodeMode::Strict).is_err());
+        assert!(decode_bytes_raw(0, bytes, DecodeMode::Strict).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_shutdown_with_message_in_flight() {
+        let (event_send, event_recv) = mpsc::channel();
+        let thread = std::thread::spawn(move || {
+            let replies = request(1, b"foo".to_vec(), &event_send);
+            let _ = event_send.send(Event::Closed(1));
+            replies
+        });
+        // the message is dropped without replying
+        let mut remaining = HashSet::from([1]);
+        wait_for_closed(&event_recv, &mut remaining, Duration::from_secs(10));
+        assert!(remaining.is_empty());
+        assert!(thread.join().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_parse_header() {
+        assert_eq!(parse_header(b"wsclip:get"), Some(("get", &b""[..])));
+        assert_eq!(
+            parse_header(b"wsclip:error\nfoo\nbar"),
+            Some(("error", &b"foo\nbar"[..]))
+        );
+        assert_eq!(parse_header(b"foo"), None);
+    }
+
+    #[test]
+    fn test_rate_limit() {
+        let mut rate = RateLimit::new();
+        let now = rate.start;
+        assert!(rate.allow(now, 2));
+        assert!(rate.allow(now, 2));
+        assert!(!rate.allow(now + Duration::from_millis(999), 2));
+        assert_eq!(rate.dropped, 1);
+        assert!(rate.allow(now + Duration::from_secs(1), 2));
+        assert_eq!(rate.dropped, 0);
+    }
+
+    #[test]
+    fn test_is_health_request() {
+        assert!(is_health_request(
+            b"GET /health HTTP/1.1\r\nHost: x\r\n\r\n"
+        ));
+        assert!(is_health_request(b"GET /health?x=1 HTTP/1.1\r\n\r\n"));
+        assert!(!is_health_request(b"GET / HTTP/1.1\r\n\r\n"));
+        assert!(!is_health_request(
+            b"GET /health HTTP/1.1\r\nUpgrade: websocket\r\n\r\n"
+        ));
+    }
+
+    #[test]
+    fn test_head_has_token() {
+        assert!(head_has_token(
+            b"GET /health?token=abc HTTP/1.1\r\nHost: x\r\n\r\n",
+            "abc"
+        ));
+        assert!(head_has_token(
+            b"GET /health HTTP/1.1\r\nAuthorization: Bearer abc\r\n\r\n",
+            "abc"
+        ));
+        assert!(!head_has_token(b"GET /health HTTP/1.1\r\n\r\n", "abc"));
+        assert!(!head_has_token(
+            b"GET /health?token=abd HTTP/1.1\r\n\r\n",
+            "abc"
+        ));
+    }
+
+    #[test]
+    fn test_preview() {
+        assert_eq!(preview("foo"), r#""foo""#);
+        assert_eq!(preview("foo\nbar"), r#""foo...""#);
+        assert_eq!(preview("a\tb"), r#""a\tb""#);
+        let long = "x".repeat(50);
+        assert_eq!(preview(&long), format!("\"{}...\"", "x".repeat(40)));
+    }
+
+    #[test]
+    fn test_origin_eq() {
+        assert!(origin_eq("https://example.com", "https://Example.com/"));
+        assert!(!origin_eq("https://example.com", "http://example.com"));
+    }
+}
@@ -0,0 +1,279 @@
+// SPDX-License-Identifier: MIT
+// Copyright (c) 2026 Pistonite
+
+//! Sockets the server listens on, which can be TCP or Unix domain sockets
+
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, Shutdown, SocketAddr, TcpListener, TcpStream};
+#[cfg(unix)]
+use std::os::unix::net::{UnixListener, UnixStream};
+#[cfg(unix)]
+use std::path::PathBuf;
+use std::time::Duration;
+
+use cu::pre::*;
+
+/// Address to listen on
+pub enum Address {
+    Tcp(SocketAddr),
+    #[cfg(unix)]
+    Unix(PathBuf),
+}
+
+impl Address {
+    pub fn bind(&self) -> cu::Result<Listener> {
+        match self {
+            Self::Tcp(x) => {
+                let listener = cu::check!(TcpListener::bind(x), "failed to bind to {x}")?;
+                Ok(Listener::Tcp(listener))
+            }
+            #[cfg(unix)]
+            Self::Unix(x) => {
+                let listener = match UnixListener::bind(x) {
+                    Ok(x) => x,
+                    Err(e) if e.kind() == io::ErrorKind::AddrInUse => {
+                        cu::hint!("remove '{}' if the server is not running", x.display());
+                        cu::rethrow!(e, "failed to bind to '{}'", x.display());
+                    }
+                    Err(e) => cu::rethrow!(e, "failed to bind to '{}'", x.display()),
+                };
+                Ok(Listener::Unix(listener))
+            }
+        }
+    }
+
+    /// Make a new connection to the server itself, to unblock the accepting thread.
+    /// This is a plain connection, since the websocket (or TLS) handshake is not needed
+    pub fn connect_to_self(&self) -> io::Result<()> {
+        match self {
+            Self::Tcp(x) => TcpStream::connect(self_connect_address(*x)).map(|_| ()),
+            #[cfg(unix)]
+            Self::Unix(x) => UnixStream::connect(x).map(|_| ()),
+        }
+    }
+
+    /// Remove the socket file after the server is closed
+    pub fn cleanup(&self) {
+        #[cfg(unix)]
+        if let Self::Unix(x) = self {
+            let _ = std::fs::remove_file(x);
+        }
+    }
+}
+
+impl fmt::Display for Address {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Tcp(x) => x.fmt(f),
+            #[cfg(unix)]
+            Self::Unix(x) => write!(f, "unix:{}", x.display()),
+        }
+    }
+}
+
+/// Get the address to connect to the server itself
+fn self_connect_address(address: SocketAddr) -> SocketAddr {
+    let ip = match address.ip() {
+        IpAddr::V4(x) if x.is_unspecified() => IpAddr::V4(Ipv4Addr::LOCALHOST),
+        IpAddr::V6(x) if x.is_unspecified() => IpAddr::V6(Ipv6Addr::LOCALHOST),
+        x => x,
+    };
+    SocketAddr::new(ip, address.port())
+}
+
+pub enum Listener {
+    Tcp(TcpListener),
+    #[cfg(unix)]
+    Unix(UnixListener),
+}
+
+impl Listener {
+    pub fn accept(&self) -> io::Result<Socket> {
+        match self {
+            Self::Tcp(x) => x.accept().map(|(s, _)| Socket::Tcp(s)),
+            #[cfg(unix)]
+            Self::Unix(x) => x.accept().map(|(s, _)| Socket::Unix(s)),
+        }
+    }
+}
+
+/// A connected socket
+#[derive(Debug)]
+pub enum Socket {
+    Tcp(TcpStream),
+    #[cfg(unix)]
+    Unix(UnixStream),
+}
+
+impl Socket {
+    pub fn try_clone(&self) -> io::Result<Self> {
+        match self {
+            Self::Tcp(s) => s.try_clone().map(Self::Tcp),
+            #[cfg(unix)]
+            Self::Unix(s) => s.try_clone().map(Self::Unix),
+        }
+    }
+
+    pub fn shutdown(&self, how: Shutdown) -> io::Result<()> {
+        match self {
+            Self::Tcp(s) => s.shutdown(how),
+            #[cfg(unix)]
+            Self::Unix(s) => s.shutdown(how),
+        }
+    }
+
+    pub fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        match self {
+            Self::Tcp(s) => s.set_read_timeout(timeout),
+            #[cfg(unix)]
+            Self::Unix(s) => s.set_read_timeout(timeout),
+        }
+    }
+
+    /// Get the address of the peer for logging
+    pub fn peer_addr(&self) -> String {
+        match self {
+            Self::Tcp(s) => match s.peer_addr() {
+                Ok(x) => x.to_string(),
+                Err(_) => "unknown address".to_string(),
+            },
+            #[cfg(unix)]
+            Self::Unix(_) => "unix socket".to_string(),
+        }
+    }
+}
+
+impl Read for Socket {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Self::Tcp(s) => s.read(buf),
+            #[cfg(unix)]
+            Self::Unix(s) => s.read(buf),
+        }
+    }
+}
+
+impl Write for Socket {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Self::Tcp(s) => s.write(buf),
+            #[cfg(unix)]
+            Self::Unix(s) => s.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Self::Tcp(s) => s.flush(),
+            #[cfg(unix)]
+            Self::Unix(s) => s.flush(),
+        }
+    }
+}
+
+/// Connection stream, which can be plain or TLS
+pub struct Stream {
+    inner: StreamInner,
+    /// Bytes already read from the stream by [`Stream::peek_request_head`],
+    /// which are returned first when reading
+    read_ahead: Vec<u8>,
+}
+
+enum StreamInner {
+    Plain(Socket),
+    Tls(Box<native_tls::TlsStream<Socket>>),
+}
+
+impl Stream {
+    pub fn plain(socket: Socket) -> Self {
+        Self {
+            inner: StreamInner::Plain(socket),
+            read_ahead: vec![],
+        }
+    }
+
+    pub fn tls(stream: native_tls::TlsStream<Socket>) -> Self {
+        Self {
+            inner: StreamInner::Tls(Box::new(stream)),
+            read_ahead: vec![],
+        }
+    }
+
+    /// Get the underlying socket
+    pub fn socket(&self) -> &Socket {
+        match &self.inner {
+            StreamInner::Plain(s) => s,
+            StreamInner::Tls(s) => s.get_ref(),
+        }
+    }
+
+    /// Read until the end of the HTTP request head (or `max` bytes), without consuming it
+    pub fn peek_request_head(&mut self, max: usize) -> io::Result<&[u8]> {
+        let mut buf = [0u8; 1024];
+        while !self.read_ahead.windows(4).any(|x| x == b"\r\n\r\n") && self.read_ahead.len() < max {
+            let n = match &mut self.inner {
+                StreamInner::Plain(s) => s.read(&mut buf)?,
+                StreamInner::Tls(s) => s.read(&mut buf)?,
+            };
+            if n == 0 {
+                break;
+            }
+            self.read_ahead.extend_from_slice(&buf[..n]);
+        }
+        Ok(&self.read_ahead)
+    }
+}
+
+impl Read for Stream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if !self.read_ahead.is_empty() {
+            let n = buf.len().min(self.read_ahead.len());
+            buf[..n].copy_from_slice(&self.read_ahead[..n]);
+            self.read_ahead.drain(..n);
+            return Ok(n);
+        }
+        match &mut self.inner {
+            StreamInner::Plain(s) => s.read(buf),
+            StreamInner::Tls(s) => s.read(buf),
+        }
+    }
+}
+
+impl Write for Stream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match &mut self.inner {
+            StreamInner::Plain(s) => s.write(buf),
+            StreamInner::Tls(s) => s.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match &mut self.inner {
+            StreamInner::Plain(s) => s.flush(),
+            StreamInner::Tls(s) => s.flush(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_self_connect_address() {
+        let address = |x: &str| x.parse::<SocketAddr>().unwrap();
+        assert_eq!(
+            self_connect_address(address("0.0.0.0:8881")),
+            address("127.0.0.1:8881")
+        );
+        assert_eq!(
+            self_connect_address(address("[::]:8881")),
+            address("[::1]:8881")
+        );
+        assert_eq!(
+            self_connect_address(address("192.168.0.2:8881")),
+            address("192.168.0.2:8881")
+        );
+    }
+}